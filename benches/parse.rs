@@ -0,0 +1,41 @@
+// Websocket message parsing, as done for every message during placement
+// storms.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use pxlsspace_api::Message;
+
+use std::hint::black_box;
+
+fn pixels(count: usize) -> String {
+	let pixels = (0..count)
+		.map(|i| format!(r#"{{"x":{},"y":{},"color":{}}}"#, i % 1000, i / 1000, i % 32))
+		.collect::<Vec<_>>()
+		.join(",");
+	format!(r#"{{"type":"pixel","pixels":[{}]}}"#, pixels)
+}
+
+fn chat() -> String {
+	r#"{"type":"chat_message","message":{"id":123456,"author":"someone","date":1650000000,"message_raw":"hello everyone, is anyone working on the flag?","purge":null,"badges":[],"authorNameColor":3,"authorWasShadowBanned":false,"strippedFaction":null}}"#.to_owned()
+}
+
+fn parse(c: &mut Criterion) {
+	let messages = [
+		("pixel", pixels(1)),
+		("pixels_100", pixels(100)),
+		("pixels_1000", pixels(1000)),
+		("chat", chat()),
+	];
+
+	let mut group = c.benchmark_group("parse");
+	for (name, text) in &messages {
+		Message::parse(text).expect("benchmark message should parse");
+
+		group.bench_with_input(BenchmarkId::new("parse", name), text, |b, text| {
+			b.iter(|| Message::parse(black_box(text)).unwrap())
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
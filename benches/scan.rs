@@ -0,0 +1,62 @@
+// Whole-board scans, which are spread across threads with the rayon feature.
+// Run with and without it to compare.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pxlsspace_api::scan;
+
+use std::hint::black_box;
+use std::time::{Duration, SystemTime};
+
+// square boards of these sizes, up to the size of the larger pxls canvases
+const SIZES: [usize; 3] = [500, 1000, 2000];
+
+// Deterministic noise, so runs are comparable.
+fn board(cells: usize, seed: u32) -> Vec<u8> {
+	let mut state = seed;
+	(0..cells)
+		.map(|_| {
+			state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+			(state >> 24) as u8
+		})
+		.collect()
+}
+
+fn timestamps(c: &mut Criterion) {
+	let canvas_start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+	let now = canvas_start + Duration::from_secs(7 * 24 * 60 * 60);
+
+	let mut group = c.benchmark_group("timestamps");
+	for size in SIZES {
+		let cells = size * size;
+		let heatmap = board(cells, 1);
+		// roughly half of the board has been placed on
+		let virginmap = board(cells, 2).into_iter().map(|value| value & 1).collect::<Vec<_>>();
+
+		group.throughput(Throughput::Elements(cells as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+			b.iter(|| scan::timestamps(black_box(&heatmap), black_box(&virginmap), now, canvas_start))
+		});
+	}
+	group.finish();
+}
+
+fn changed(c: &mut Criterion) {
+	let mut group = c.benchmark_group("changed");
+	for size in SIZES {
+		let cells = size * size;
+		let previous = board(cells, 3);
+		// about one in a hundred pixels differs
+		let current = previous.iter().enumerate()
+			.map(|(index, &color)| if index % 97 == 0 { color.wrapping_add(1) } else { color })
+			.collect::<Vec<_>>();
+
+		group.throughput(Throughput::Elements(cells as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+			b.iter(|| scan::changed(black_box(&current), black_box(&previous), size))
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, timestamps, changed);
+criterion_main!(benches);
@@ -0,0 +1,175 @@
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{Client, Pixel, Rect};
+use crate::client::UNPLACEABLE;
+use crate::export::serialize_millis;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActivitySample {
+	#[serde(serialize_with = "serialize_millis")]
+	pub start: SystemTime,
+	pub count: u64,
+}
+
+// Buckets pixel placements into fixed size time windows.
+#[derive(Debug)]
+pub struct ActivityAggregator {
+	bucket_size: Duration,
+	region: Option<Rect>,
+	// maximum number of buckets to retain
+	capacity: Option<usize>,
+	// keyed by bucket number since the unix epoch
+	buckets: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl ActivityAggregator {
+	pub fn new(bucket_size: Duration) -> Self {
+		assert!(!bucket_size.is_zero(), "Activity bucket size must be non-zero");
+		Self {
+			bucket_size,
+			region: None,
+			capacity: None,
+			buckets: Mutex::new(BTreeMap::new()),
+		}
+	}
+
+	pub fn per_minute() -> Self {
+		Self::new(Duration::from_secs(60))
+	}
+
+	pub fn per_hour() -> Self {
+		Self::new(Duration::from_secs(60 * 60))
+	}
+
+	// Only counts pixels placed within the region.
+	pub fn region(mut self, region: Rect) -> Self {
+		self.region = Some(region);
+		self
+	}
+
+	// Discards the oldest buckets once there are more than `buckets`.
+	pub fn capacity(mut self, buckets: usize) -> Self {
+		self.capacity = Some(buckets);
+		self
+	}
+
+	pub fn bucket_size(&self) -> Duration {
+		self.bucket_size
+	}
+
+	fn bucket_of(&self, time: SystemTime) -> u64 {
+		let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+		u64::try_from(since_epoch.as_nanos() / self.bucket_size.as_nanos()).unwrap()
+	}
+
+	fn bucket_start(&self, bucket: u64) -> SystemTime {
+		let nanos = self.bucket_size.as_nanos() * u128::from(bucket);
+		UNIX_EPOCH + Duration::from_nanos(u64::try_from(nanos).unwrap())
+	}
+
+	pub fn record(&self, pixels: &[Pixel], time: SystemTime) {
+		let count = pixels.iter()
+			.filter(|pixel| self.region.is_none_or(|region| region.contains(pixel.position)))
+			.count();
+
+		if count == 0 {
+			return;
+		}
+
+		let bucket = self.bucket_of(time);
+		let mut buckets = self.buckets.lock().unwrap();
+		*buckets.entry(bucket).or_default() += u64::try_from(count).unwrap();
+
+		if let Some(capacity) = self.capacity {
+			while buckets.len() > capacity {
+				buckets.pop_first();
+			}
+		}
+	}
+
+	pub fn count_at(&self, time: SystemTime) -> u64 {
+		let bucket = self.bucket_of(time);
+		self.buckets.lock().unwrap().get(&bucket).copied().unwrap_or(0)
+	}
+
+	// Every bucket from the first recorded placement to the last, including
+	// empty buckets in between.
+	pub fn series(&self) -> Vec<ActivitySample> {
+		let buckets = self.buckets.lock().unwrap();
+		let (first, last) = match (buckets.keys().next(), buckets.keys().next_back()) {
+			(Some(&first), Some(&last)) => (first, last),
+			_ => return vec![],
+		};
+
+		(first..=last)
+			.map(|bucket| ActivitySample {
+				start: self.bucket_start(bucket),
+				count: buckets.get(&bucket).copied().unwrap_or(0),
+			})
+			.collect()
+	}
+
+	pub fn clear(&self) {
+		self.buckets.lock().unwrap().clear();
+	}
+
+	// Records placements from the client as they arrive.
+	pub async fn run(&self, client: &Client) {
+		let pixels = client.pixels();
+		futures_util::pin_mut!(pixels);
+		while let Some(pixel) = pixels.next().await {
+			self.record(std::slice::from_ref(&pixel), SystemTime::now());
+		}
+	}
+}
+
+// How much of the canvas has been drawn on, counting placeable pixels only.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CanvasActivity {
+	pub placeable: usize,
+	// placed on at least once
+	pub touched: usize,
+	// a different color than at the start of the canvas
+	pub changed: usize,
+}
+
+impl CanvasActivity {
+	pub(crate) fn measure(mask: &[u8], virginmap: &[u8], colors: &[u8], initial: &[u8]) -> Self {
+		let mut activity = Self::default();
+		let cells = mask.iter().zip(virginmap).zip(colors.iter().zip(initial));
+		for ((&mask, &virgin), (current, initial)) in cells {
+			if mask == UNPLACEABLE {
+				continue;
+			}
+
+			activity.placeable += 1;
+			if virgin == 0 {
+				activity.touched += 1;
+			}
+			if current != initial {
+				activity.changed += 1;
+			}
+		}
+		activity
+	}
+
+	fn fraction(&self, count: usize) -> f64 {
+		match self.placeable {
+			0 => 0.0,
+			placeable => count as f64 / placeable as f64,
+		}
+	}
+
+	pub fn touched_fraction(&self) -> f64 {
+		self.fraction(self.touched)
+	}
+
+	pub fn changed_fraction(&self) -> f64 {
+		self.fraction(self.changed)
+	}
+}
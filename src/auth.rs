@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use hyper::header::HeaderValue;
+use serde::Deserialize;
+
+use crate::{Client, RequestError};
+
+// name of the cookie pxls uses to identify a logged in user
+pub(crate) const TOKEN_COOKIE: &str = "pxls-token";
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Session {
+	token: String,
+	cookie: HeaderValue,
+}
+
+impl Session {
+	// Tokens have to be usable as a cookie value, so they can't be empty or
+	// contain whitespace, control characters, quotes, commas, semicolons or
+	// backslashes.
+	pub fn new(token: String) -> Result<Self, AuthError> {
+		let valid = !token.is_empty() && token.bytes().all(|byte| {
+			byte.is_ascii_graphic() && !matches!(byte, b'"' | b',' | b';' | b'\\')
+		});
+		if !valid {
+			return Err(AuthError::InvalidToken);
+		}
+
+		let cookie = HeaderValue::from_str(&format!("{}={}", TOKEN_COOKIE, token))
+			.map_err(|_| AuthError::InvalidToken)?;
+		Ok(Self { token, cookie })
+	}
+
+	pub fn token(&self) -> &str {
+		&self.token
+	}
+
+	pub(crate) fn cookie(&self) -> HeaderValue {
+		self.cookie.clone()
+	}
+}
+
+// the token is as good as a password, so keep it out of logs
+impl std::fmt::Debug for Session {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Session").field("token", &"<redacted>").finish()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct SignIn {
+	pub session: Session,
+	// the account doesn't exist yet and needs a username before it can be used
+	pub signup_required: bool,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+	UnknownService(String),
+	// the token can't be sent as a cookie, see `Session::new`
+	InvalidToken,
+	InvalidCallback(url::Url),
+	Rejected { error: String, message: String },
+	Request(RequestError),
+}
+
+impl From<RequestError> for AuthError {
+	fn from(error: RequestError) -> Self {
+		AuthError::Request(error)
+	}
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SignInRedirect {
+	pub url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum AuthResponse {
+	Success {
+		token: String,
+		signup: bool,
+	},
+	Failure {
+		error: String,
+		message: String,
+	},
+}
+
+impl From<AuthResponse> for Result<SignIn, AuthError> {
+	fn from(response: AuthResponse) -> Self {
+		match response {
+			AuthResponse::Success { token, signup } => Ok(SignIn {
+				session: Session::new(token)?,
+				signup_required: signup,
+			}),
+			AuthResponse::Failure { error, message } => {
+				Err(AuthError::Rejected { error, message })
+			},
+		}
+	}
+}
+
+// Provides fresh credentials once the current session stops working, such as
+// by signing in again or reading a newer token from storage.
+#[async_trait]
+pub trait Reauthenticator: Send + Sync {
+	async fn reauthenticate(
+		&self,
+		client: &Client,
+		expired: Option<Session>,
+	) -> Option<Session>;
+}
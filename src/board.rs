@@ -0,0 +1,257 @@
+use bytes::{Bytes, BytesMut};
+
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+use std::slice::ChunksExact;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+use crate::{BoardInfo, Pos};
+
+#[derive(Debug)]
+enum Cells {
+	Heap(Box<[AtomicU8]>),
+	#[cfg(feature = "mmap")]
+	Mapped(MappedCells),
+}
+
+// Cells which live in a memory mapped file.
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+struct MappedCells {
+	// only ever accessed through `cells`, it's kept here so the mapping stays
+	// alive
+	_map: memmap2::MmapMut,
+	cells: *const AtomicU8,
+	len: usize,
+}
+
+// SAFETY: the mapping is owned by the struct and only accessed through
+// atomics, which are safe to share between threads.
+#[cfg(feature = "mmap")]
+unsafe impl Send for MappedCells {}
+#[cfg(feature = "mmap")]
+unsafe impl Sync for MappedCells {}
+
+// Maps a new file holding `contents` at `path`. Whatever was at `path` is
+// replaced rather than written to, since an older board may still have it
+// mapped and resizing or overwriting it would change memory out from under
+// that board.
+#[cfg(feature = "mmap")]
+pub(crate) fn map_file(path: &std::path::Path, contents: &[u8]) -> std::io::Result<memmap2::MmapMut> {
+	use std::sync::atomic::AtomicUsize;
+	static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+
+	let mut temporary = path.as_os_str().to_owned();
+	let id = NEXT_FILE.fetch_add(1, Ordering::Relaxed);
+	temporary.push(format!(".{}.{}.tmp", std::process::id(), id));
+	let temporary = std::path::PathBuf::from(temporary);
+
+	let file = std::fs::OpenOptions::new()
+		.read(true)
+		.write(true)
+		.create_new(true)
+		.open(&temporary)?;
+
+	let mapped = file.set_len(u64::try_from(contents.len()).unwrap())
+		// SAFETY: the file was just created under a name nothing else uses,
+		// so nobody else has it mapped. Other processes modifying it
+		// underneath us is outside of what we can guard against.
+		.and_then(|_| unsafe { memmap2::MmapMut::map_mut(&file) })
+		.and_then(|mut map| {
+			map.copy_from_slice(contents);
+			std::fs::rename(&temporary, path)?;
+			Ok(map)
+		});
+
+	if mapped.is_err() {
+		let _ = std::fs::remove_file(&temporary);
+	}
+	mapped
+}
+
+impl Cells {
+	fn as_slice(&self) -> &[AtomicU8] {
+		match self {
+			Cells::Heap(cells) => cells,
+			// SAFETY: AtomicU8 has the same layout as u8, the pointer came from
+			// a mutable borrow of the mapping, and the mapping lives as long as
+			// self.
+			#[cfg(feature = "mmap")]
+			Cells::Mapped(mapped) => unsafe {
+				std::slice::from_raw_parts(mapped.cells, mapped.len)
+			},
+		}
+	}
+}
+
+// Board colors stored in atomic cells so that pixels can be updated and read
+// concurrently without either side waiting on a lock. Reads of several cells
+// aren't synchronized with each other, so a copy taken while pixels are being
+// placed can contain some updates of a batch but not others.
+#[derive(Debug)]
+pub struct AtomicBoard {
+	width: usize,
+	height: usize,
+	cells: Cells,
+	// counts batches of updates, so that copies can tell if they're current
+	version: AtomicU64,
+}
+
+impl AtomicBoard {
+	pub fn new(width: usize, height: usize, colors: &[u8]) -> Self {
+		let cells = colors.iter()
+			.map(|&color| AtomicU8::new(color))
+			.collect();
+
+		Self { width, height, cells: Cells::Heap(cells), version: AtomicU64::new(0) }
+	}
+
+	// Keeps the board in the file at `path` rather than in memory. The file
+	// is replaced with `colors` and from then on holds the current board.
+	#[cfg(feature = "mmap")]
+	pub fn mapped(
+		width: usize,
+		height: usize,
+		colors: &[u8],
+		path: &std::path::Path,
+	) -> std::io::Result<Self> {
+		if colors.is_empty() {
+			// empty files can't be mapped
+			return Ok(Self::new(width, height, colors));
+		}
+
+		let mut map = map_file(path, colors)?;
+		let mapped = MappedCells {
+			cells: map.as_mut_ptr() as *const AtomicU8,
+			len: map.len(),
+			_map: map,
+		};
+
+		Ok(Self { width, height, cells: Cells::Mapped(mapped), version: AtomicU64::new(0) })
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	pub fn len(&self) -> usize {
+		self.cells.as_slice().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.cells.as_slice().is_empty()
+	}
+
+	pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+		if !Pos::new(x, y).is_within(self.width, self.height) {
+			return None;
+		}
+
+		self.cells.as_slice().get(Pos::new(x, y).index(self.width))
+			.map(|cell| cell.load(Ordering::Relaxed))
+	}
+
+	// Applies a batch of (index, color) updates.
+	pub(crate) fn update(&self, updates: &[(usize, u8)]) {
+		let cells = self.cells.as_slice();
+		for &(index, color) in updates {
+			if let Some(cell) = cells.get(index) {
+				cell.store(color, Ordering::Relaxed);
+			}
+		}
+		self.version.fetch_add(1, Ordering::Release);
+	}
+
+	// Changes whenever a batch of updates is applied.
+	pub(crate) fn version(&self) -> u64 {
+		self.version.load(Ordering::Acquire)
+	}
+
+	pub fn to_bytes(&self) -> Bytes {
+		self.copy().freeze()
+	}
+
+	pub(crate) fn copy(&self) -> BytesMut {
+		let cells = self.cells.as_slice();
+		let mut bytes = BytesMut::with_capacity(cells.len());
+		bytes.extend(cells.iter().map(|cell| cell.load(Ordering::Relaxed)));
+		bytes
+	}
+
+	// Matches `BoardView::hash` for the same colors. Updates made while
+	// hashing may or may not be included.
+	pub fn hash(&self) -> u64 {
+		let mut hasher = Xxh3::new();
+		let mut chunk = [0; 4096];
+		for cells in self.cells.as_slice().chunks(chunk.len()) {
+			for (byte, cell) in chunk.iter_mut().zip(cells) {
+				*byte = cell.load(Ordering::Relaxed);
+			}
+			hasher.update(&chunk[..cells.len()]);
+		}
+		hasher.digest()
+	}
+}
+
+// The board colors as they were at one instant, along with the info they
+// belong to. Holding on to a view doesn't hold up updates, which go to the
+// live board rather than the view.
+#[derive(Debug, Clone)]
+pub struct BoardView {
+	info: Arc<BoardInfo>,
+	colors: Arc<BytesMut>,
+}
+
+impl BoardView {
+	pub(crate) fn new(info: Arc<BoardInfo>, colors: Arc<BytesMut>) -> Self {
+		Self { info, colors }
+	}
+
+	pub fn info(&self) -> &BoardInfo {
+		&self.info
+	}
+
+	pub fn width(&self) -> usize {
+		self.info.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.info.height
+	}
+
+	pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+		if !Pos::new(x, y).is_within(self.width(), self.height()) {
+			return None;
+		}
+
+		self.colors.get(Pos::new(x, y).index(self.width())).copied()
+	}
+
+	pub fn row(&self, y: usize) -> Option<&[u8]> {
+		let start = y.checked_mul(self.width())?;
+		self.colors.get(start..start + self.width())
+	}
+
+	// Rows from top to bottom.
+	pub fn rows(&self) -> ChunksExact<'_, u8> {
+		self.colors.chunks_exact(self.width().max(1))
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.colors
+	}
+
+	// An xxh3 hash of the colors, for telling whether two boards match.
+	pub fn hash(&self) -> u64 {
+		xxh3_64(&self.colors)
+	}
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+
+// Identifies a canvas, which is a number optionally followed by a suffix for
+// canvases which were restarted or run alongside another (e.g. "56", "17a").
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[serde(transparent)]
+pub struct CanvasCode(String);
+
+impl CanvasCode {
+	pub fn new<S: Into<String>>(code: S) -> Self {
+		Self(code.into())
+	}
+
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	fn split(&self) -> (Option<u32>, &str) {
+		let digits = self.0.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.0.len());
+		let (number, suffix) = self.0.split_at(digits);
+		(number.parse().ok(), suffix)
+	}
+
+	// The numeric part of the code, `None` if it doesn't start with a number.
+	pub fn number(&self) -> Option<u32> {
+		self.split().0
+	}
+
+	// Whatever follows the number, such as "a" in "17a".
+	pub fn suffix(&self) -> &str {
+		self.split().1
+	}
+}
+
+// Codes are ordered by their number, then suffix, with codes that have no
+// number last.
+impl Ord for CanvasCode {
+	fn cmp(&self, other: &Self) -> Ordering {
+		let (number, suffix) = self.split();
+		let (other_number, other_suffix) = other.split();
+		match (number, other_number) {
+			(Some(number), Some(other_number)) => number.cmp(&other_number),
+			(Some(_), None) => Ordering::Less,
+			(None, Some(_)) => Ordering::Greater,
+			(None, None) => Ordering::Equal,
+		}
+		.then_with(|| suffix.cmp(other_suffix))
+		.then_with(|| self.0.cmp(&other.0))
+	}
+}
+
+impl PartialOrd for CanvasCode {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl std::fmt::Display for CanvasCode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl AsRef<str> for CanvasCode {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl From<String> for CanvasCode {
+	fn from(code: String) -> Self {
+		Self(code)
+	}
+}
+
+impl From<&str> for CanvasCode {
+	fn from(code: &str) -> Self {
+		Self(code.to_string())
+	}
+}
+
+impl std::str::FromStr for CanvasCode {
+	type Err = std::convert::Infallible;
+
+	fn from_str(code: &str) -> Result<Self, Self::Err> {
+		Ok(Self::from(code))
+	}
+}
+
+impl PartialEq<str> for CanvasCode {
+	fn eq(&self, other: &str) -> bool {
+		self.0 == other
+	}
+}
+
+impl PartialEq<&str> for CanvasCode {
+	fn eq(&self, other: &&str) -> bool {
+		self.0 == *other
+	}
+}
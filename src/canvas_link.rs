@@ -0,0 +1,88 @@
+use url::Url;
+
+use crate::Pos;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanvasLinkError {
+	InvalidUrl(url::ParseError),
+	// the link doesn't have both an `x` and a `y`
+	MissingPosition,
+	InvalidNumber { key: String, value: String },
+}
+
+// A link to a location on the canvas: `#x=<x>&y=<y>&scale=<scale>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasLink {
+	pub position: Pos,
+	// how far the site should zoom in, left to the site if not given
+	pub scale: Option<f64>,
+}
+
+impl CanvasLink {
+	pub fn new(position: Pos) -> Self {
+		Self { position, scale: None }
+	}
+
+	pub fn scale(mut self, scale: f64) -> Self {
+		self.scale = Some(scale);
+		self
+	}
+
+	pub fn parse(link: &Url) -> Result<Self, CanvasLinkError> {
+		let fragment = link.fragment().unwrap_or_default();
+		let parameters = url::form_urlencoded::parse(fragment.as_bytes())
+			.collect::<Vec<_>>();
+
+		let number = |key: &str| {
+			parameters.iter()
+				.find(|(name, _)| name == key)
+				.filter(|(_, value)| !value.is_empty())
+				.map(|(_, value)| {
+					value.parse::<f64>().ok()
+						.filter(|number| number.is_finite() && *number >= 0.0)
+						.ok_or_else(|| CanvasLinkError::InvalidNumber {
+							key: key.to_string(),
+							value: value.to_string(),
+						})
+				})
+				.transpose()
+		};
+
+		// the site allows fractional coordinates when zoomed out
+		let x = number("x")?.ok_or(CanvasLinkError::MissingPosition)?;
+		let y = number("y")?.ok_or(CanvasLinkError::MissingPosition)?;
+
+		Ok(Self {
+			position: Pos::new(x as usize, y as usize),
+			scale: number("scale")?,
+		})
+	}
+
+	pub fn to_url(&self, site: &Url) -> Url {
+		let mut fragment = url::form_urlencoded::Serializer::new(String::new());
+		fragment.append_pair("x", &self.position.x.to_string());
+		fragment.append_pair("y", &self.position.y.to_string());
+		if let Some(scale) = self.scale {
+			fragment.append_pair("scale", &scale.to_string());
+		}
+
+		let mut url = site.clone();
+		url.set_fragment(Some(&fragment.finish()));
+		url
+	}
+}
+
+impl From<Pos> for CanvasLink {
+	fn from(position: Pos) -> Self {
+		Self::new(position)
+	}
+}
+
+impl std::str::FromStr for CanvasLink {
+	type Err = CanvasLinkError;
+
+	fn from_str(link: &str) -> Result<Self, Self::Err> {
+		let link = Url::parse(link).map_err(CanvasLinkError::InvalidUrl)?;
+		Self::parse(&link)
+	}
+}
@@ -0,0 +1,45 @@
+use tokio::sync::Mutex;
+
+use std::io;
+use std::path::PathBuf;
+
+use crate::log_writer::{LogWriter, timestamped_line};
+use crate::messages::Message;
+
+// Archives chat to a file with one JSON object per line, holding the time the
+// message was received in milliseconds and the message itself. Chat messages,
+// purges and chat bans are recorded.
+#[derive(Debug)]
+pub struct ChatLog {
+	writer: Mutex<LogWriter>,
+}
+
+impl ChatLog {
+	pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+		Self {
+			writer: Mutex::new(LogWriter::new(path.into())),
+		}
+	}
+
+	// Starts a new file once the current one would grow past `max_size`
+	// bytes, keeping up to `keep` old files alongside it.
+	pub fn rotate(mut self, max_size: u64, keep: usize) -> Self {
+		self.writer.get_mut().rotate(max_size, keep);
+		self
+	}
+
+	pub(crate) fn records(message: &Message) -> bool {
+		matches!(
+			message,
+			Message::ChatMessage { .. }
+			| Message::ChatPurge { .. }
+			| Message::ChatPurgeSpecific { .. }
+			| Message::ChatBan { .. }
+		)
+	}
+
+	pub(crate) async fn record(&self, message: &Message) -> io::Result<()> {
+		let line = timestamped_line(message)?;
+		self.writer.lock().await.append(&line).await
+	}
+}
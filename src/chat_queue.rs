@@ -0,0 +1,116 @@
+use tokio::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// How long after sending a message a cooldown can still be for it. Messages
+// the server hasn't echoed back by then are taken to have been delivered.
+const REJECTION_WINDOW: Duration = Duration::from_secs(5);
+
+// Outgoing chat messages waiting to be sent, paced so that the server's chat
+// rate limit is respected.
+#[derive(Debug, Default)]
+pub(crate) struct ChatQueue {
+	pending: VecDeque<(String, Instant)>,
+	// sent but neither echoed back by the server nor rejected yet, oldest
+	// first
+	unconfirmed: VecDeque<(String, Instant)>,
+	ready_at: Option<Instant>,
+	interval: Duration,
+}
+
+impl ChatQueue {
+	pub fn new(interval: Duration) -> Self {
+		Self { interval, ..Default::default() }
+	}
+
+	pub fn set_interval(&mut self, interval: Duration) {
+		self.interval = interval;
+	}
+
+	pub fn push(&mut self, message: String) -> usize {
+		self.pending.push_back((message, Instant::now()));
+		self.pending.len()
+	}
+
+	pub fn len(&self) -> usize {
+		self.pending.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+
+	pub fn clear(&mut self) -> Vec<String> {
+		self.pending.drain(..).map(|(message, _)| message).collect()
+	}
+
+	// Drops messages which have been waiting longer than `max_age`.
+	pub fn expire(&mut self, max_age: Duration) {
+		let now = Instant::now();
+		self.pending.retain(|(_, queued_at)| now.duration_since(*queued_at) < max_age);
+	}
+
+	// When the next message should be sent, if there is one.
+	pub fn due(&self) -> Option<Instant> {
+		if self.pending.is_empty() {
+			None
+		} else {
+			let now = Instant::now();
+			Some(self.ready_at.map_or(now, |ready_at| ready_at.max(now)))
+		}
+	}
+
+	pub fn take(&mut self) -> Option<String> {
+		let (message, _) = self.pending.pop_front()?;
+		let now = Instant::now();
+		self.forget_delivered(now);
+		self.unconfirmed.push_back((message.clone(), now));
+		self.ready_at = Some(now + self.interval);
+		Some(message)
+	}
+
+	// Puts back a message which could not be sent.
+	pub fn unsend(&mut self, message: String) {
+		if let Some(index) = self.unconfirmed.iter().rposition(|(sent, _)| *sent == message) {
+			self.unconfirmed.remove(index);
+		}
+		self.pending.push_front((message, Instant::now()));
+	}
+
+	// The server has echoed a message back, so it was delivered.
+	pub fn confirmed(&mut self, message: &str) {
+		if let Some(index) = self.unconfirmed.iter().position(|(sent, _)| sent == message) {
+			self.unconfirmed.remove(index);
+		}
+	}
+
+	// The server refused a message for being sent too quickly. Cooldowns
+	// normally carry the refused message, which is queued again to be sent
+	// once the cooldown has passed. Servers which send their rate limit
+	// notice instead are taken to be refusing the latest message. Cooldowns
+	// for anything else, such as messages sent directly, leave the queue
+	// alone apart from waiting them out.
+	pub fn rate_limited(&mut self, cooldown: Duration, message: &str, ratelimit_message: Option<&str>) {
+		let now = Instant::now();
+		self.ready_at = Some(now + cooldown);
+		self.forget_delivered(now);
+
+		let refused = match self.unconfirmed.iter().rposition(|(sent, _)| sent == message) {
+			Some(index) => self.unconfirmed.remove(index),
+			None if ratelimit_message == Some(message) => self.unconfirmed.pop_back(),
+			None => None,
+		};
+
+		if let Some((message, _)) = refused {
+			self.pending.push_front((message, now));
+		}
+	}
+
+	fn forget_delivered(&mut self, now: Instant) {
+		let window = self.interval.max(REJECTION_WINDOW);
+		while self.unconfirmed.front().is_some_and(|(_, sent_at)| now.duration_since(*sent_at) >= window) {
+			self.unconfirmed.pop_front();
+		}
+	}
+}
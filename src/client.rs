@@ -1,44 +1,346 @@
 use chrono::{DateTime, TimeZone};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use bytes::{Bytes, BytesMut};
+use brotli_decompressor::Decompressor as BrotliDecoder;
+use flate2::read::{GzDecoder, ZlibDecoder};
 use hyper::client::HttpConnector;
+use hyper::StatusCode;
+use hyper::header::{self, HeaderValue};
 use hyper_openssl::HttpsConnector;
-use serde::Deserialize;
-use tokio::sync::{Mutex, RwLock};
+use openssl::ssl::SslConnector;
+use serde::{Deserialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_openssl::SslStream;
+use tokio::sync::{Mutex, Notify, RwLock, watch, broadcast::{self, error::RecvError}};
 use url::Url;
-use tokio_tungstenite::{connect_async, tungstenite::Error};
+use tokio::time::Instant;
+use tokio_tungstenite::{client_async, WebSocketStream, tungstenite::{Error, Message as WebsocketMessage, client::IntoClientRequest, protocol::frame::coding::CloseCode}};
 
-use futures_util::StreamExt;
+use futures_util::{SinkExt, Stream, StreamExt, stream::SplitSink};
 
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{SystemTime, Duration};
 
-use crate::Pixel;
+use crate::{AtomicBoard, AuthError, BoardView, CanvasActivity, CanvasCode, Dump, DumpError, CanvasLink, ChatLog, EventLog, FixedDelay, Reconnect, ReconnectCause, ReconnectPolicy, Capabilities, CompatProfile, EmojiSet, Pixel, Pos, Bandwidth, RateLimiter, Rect, Reauthenticator, RequestClass, RequestInterceptor, Session, SignIn, Snapshot, Template, TemplateProgress, TemplateWatch, Timestamps};
+use crate::template::TemplateTracker;
+use crate::scan;
+use crate::user_state::UserState;
+use crate::user_count::{UserCountHistory, UserCountSample, UserCountSummary};
+use crate::placement_rate::{PlacementRate, RateTracker};
+use crate::notify::{Notice, NotificationSink, NotifyError};
+use crate::connector::Connector;
+use crate::deflate::{self, Inflate};
+use crate::resolve::{self, AddressFamily, HyperResolver, Resolver, SystemResolver};
+#[cfg(feature = "image")]
+use crate::{TemplateLink, TemplateLoadError};
+use crate::auth::{AuthResponse, SignInRedirect};
+use crate::chat_queue::ChatQueue;
+use crate::client_set::Stagger;
+use crate::lookup_cache::LookupCache;
+use crate::disk_cache::{DiskCache, DiskEntry};
+#[cfg(feature = "mmap")]
+use crate::disk_cache::path_component;
+use crate::placement_queue::PlacementQueue;
+use crate::tls::{Fingerprint, TlsConfig};
 use crate::event_handler::EventHandler;
-use crate::messages::Message;
+use crate::messages::{ClientMessage, Message, ParseError, PlacementOverrides};
+
+// Cached entries, in the order their locks are taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Slot {
+	Info,
+	Colors,
+	Board,
+	Initial,
+	Mask,
+	Heatmap,
+	Virginmap,
+	Timestamps,
+	CreatedAt,
+}
 
-type Cache<T> = Mutex<Option<Arc<RwLock<T>>>>;
+impl Slot {
+	fn kind(self) -> Option<CacheKind> {
+		match self {
+			Slot::Info => Some(CacheKind::Info),
+			Slot::Colors => Some(CacheKind::Colors),
+			Slot::Board => Some(CacheKind::Board),
+			Slot::Initial => Some(CacheKind::Initial),
+			Slot::Mask => Some(CacheKind::Mask),
+			Slot::Heatmap => Some(CacheKind::Heatmap),
+			Slot::Virginmap => Some(CacheKind::Virginmap),
+			Slot::Timestamps => Some(CacheKind::Timestamps),
+			Slot::CreatedAt => None,
+		}
+	}
+}
+
+const SLOT_COUNT: usize = Slot::CreatedAt as usize + 1;
+
+// A cached value which can be read without waiting on anything. Whoever
+// replaces or modifies the value must hold the lock for its slot so that
+// concurrent writers don't lose each other's changes.
+struct Cache<T> {
+	slot: Slot,
+	value: ArcSwapOption<T>,
+	// when the value was last fetched or confirmed unchanged by the server,
+	// live updates don't count
+	fetched_at: ArcSwapOption<Instant>,
+}
+
+impl<T> Cache<T> {
+	fn new(slot: Slot) -> Self {
+		Self { slot, value: ArcSwapOption::empty(), fetched_at: ArcSwapOption::empty() }
+	}
+
+	fn get(&self) -> Option<Arc<T>> {
+		self.value.load_full()
+	}
+
+	fn set(&self, value: Option<T>) {
+		let fetched_at = value.as_ref().map(|_| Arc::new(Instant::now()));
+		self.value.store(value.map(Arc::new));
+		self.fetched_at.store(fetched_at);
+	}
+
+	fn set_arc(&self, value: Arc<T>, fetched_at: Option<Instant>) {
+		self.value.store(Some(value));
+		self.fetched_at.store(fetched_at.map(Arc::new));
+	}
+
+	// The server has confirmed the value is still current.
+	fn touch(&self) {
+		if self.value.load().is_some() {
+			self.fetched_at.store(Some(Arc::new(Instant::now())));
+		}
+	}
+
+	fn fetched_at(&self) -> Option<Instant> {
+		self.fetched_at.load().as_deref().copied()
+	}
+
+	// Replaces the cached value with a modified copy. Readers see either the
+	// old value or the new one, never nothing, and those already holding the
+	// old value keep seeing it unchanged.
+	fn modify<F: FnOnce(&mut T)>(&self, f: F) where T: Clone {
+		if let Some(current) = self.value.load_full() {
+			let mut value = T::clone(&current);
+			f(&mut value);
+			self.value.store(Some(Arc::new(value)));
+		}
+	}
+}
+
+// A copy of the live board, handed out by `Client::colors` until the board
+// changes so that reads between batches of pixels don't each cost a copy.
+struct ColorSnapshot {
+	board: Weak<AtomicBoard>,
+	version: u64,
+	colors: Arc<BytesMut>,
+}
+
+impl ColorSnapshot {
+	fn is_current(&self, board: &Arc<AtomicBoard>) -> bool {
+		std::ptr::eq(self.board.as_ptr(), Arc::as_ptr(board)) && self.version == board.version()
+	}
+}
+// the websocket is either a plain tcp stream or one wrapped in tls
+trait Transport: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Transport for T {}
+
+type Writer = SplitSink<WebSocketStream<Box<dyn Transport>>, WebsocketMessage>;
+
+#[derive(Default, Clone)]
+struct Validators {
+	etag: Option<HeaderValue>,
+	last_modified: Option<HeaderValue>,
+	// until when the server says the response can be reused without asking
+	fresh_until: Option<Instant>,
+}
 
-#[derive(Default)]
 struct ClientCache {
 	info: Cache<BoardInfo>,
-	colors: Cache<Vec<u8>>,
-	initial: Cache<Vec<u8>>,
-	mask: Cache<Vec<u8>>,
-	timestamps: Cache<Vec<u32>>,
+	colors: Cache<ColorSnapshot>,
+	board: Cache<AtomicBoard>,
+	initial: Cache<Bytes>,
+	mask: Cache<Bytes>,
+	heatmap: Cache<BytesMut>,
+	virginmap: Cache<BytesMut>,
+	timestamps: Cache<Timestamps>,
 	created_at: Cache<SystemTime>,
+	locks: [Mutex<()>; SLOT_COUNT],
+	validators: Mutex<HashMap<&'static str, Validators>>,
 	// TODO: user count can definitely be here
 }
 
+impl Default for ClientCache {
+	fn default() -> Self {
+		Self {
+			info: Cache::new(Slot::Info),
+			colors: Cache::new(Slot::Colors),
+			board: Cache::new(Slot::Board),
+			initial: Cache::new(Slot::Initial),
+			mask: Cache::new(Slot::Mask),
+			heatmap: Cache::new(Slot::Heatmap),
+			virginmap: Cache::new(Slot::Virginmap),
+			timestamps: Cache::new(Slot::Timestamps),
+			created_at: Cache::new(Slot::CreatedAt),
+			locks: Default::default(),
+			validators: Mutex::new(HashMap::new()),
+		}
+	}
+}
+
+impl ClientCache {
+	// Takes the locks for every given slot at once, always in slot order.
+	// This is the only way cache locks are taken and it is never called while
+	// holding the guard from a previous call, so no two tasks can end up
+	// waiting on each other. Anything needed from other entries, such as
+	// board info, must be loaded before calling this.
+	async fn lock(&self, slots: &[Slot]) -> Vec<tokio::sync::MutexGuard<'_, ()>> {
+		let mut slots = slots.to_vec();
+		slots.sort_unstable();
+		slots.dedup();
+
+		let mut guards = Vec::with_capacity(slots.len());
+		for slot in slots {
+			guards.push(self.locks[slot as usize].lock().await);
+		}
+		guards
+	}
+}
+
+#[derive(Debug, Default)]
+struct Paused {
+	paused: bool,
+	// also holds messages which arrived while resuming, so that they aren't
+	// delivered ahead of older ones
+	buffered: VecDeque<Message>,
+}
+
 #[derive(Default)]
 pub struct ClientBuidler {
 	site_base: Option<Url>,
-	event_handler: Option<Arc<dyn EventHandler>>,
-	reconnect_time: Option<Duration>,
+	buffer_base: Option<Url>,
+	#[cfg(unix)]
+	unix_socket: Option<PathBuf>,
+	event_handler: Option<Box<dyn EventHandler>>,
+	reconnect_policy: Option<Box<dyn ReconnectPolicy>>,
+	board_update_window: Option<Duration>,
+	heartbeat: Option<Duration>,
+	coalesce_pixels: bool,
+	placement_rate_window: Option<Duration>,
+	rate_regions: Vec<(String, Rect)>,
+	user_count_history: Option<usize>,
+	chat_interval: Option<Duration>,
+	compat: Option<CompatProfile>,
+	parse_mode: Option<ParseMode>,
+	session: Option<Session>,
+	reauthenticator: Option<Arc<dyn Reauthenticator>>,
+	resolver: Option<Arc<dyn Resolver>>,
+	address_family: Option<AddressFamily>,
+	websocket_compression: bool,
+	offline_policy: Option<OfflinePolicy>,
+	refuse_unplaceable: bool,
+	stale_info: bool,
+	revalidate_after: Option<Duration>,
+	honor_cache_control: bool,
+	pause_policy: Option<PausePolicy>,
+	rate_limiter: Option<Arc<RateLimiter>>,
+	bandwidth: Option<Arc<Bandwidth>>,
+	retry_policy: Option<RetryPolicy>,
+	cache_dir: Option<PathBuf>,
+	lookup_cache_size: Option<usize>,
+	board_storage: Option<BoardStorage>,
+	tls: TlsConfig,
+	request_interceptor: Option<Arc<dyn RequestInterceptor>>,
+	chat_log: Option<ChatLog>,
+	event_log: Option<EventLog>,
+	notification_sinks: Vec<Box<dyn NotificationSink>>,
+}
+
+// How the client deals with data that doesn't match what it expects.
+// Lenient mode fills in defaults for missing non-critical fields and falls
+// back on the compatibility profile, strict mode reports every mismatch
+// through `handle_error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+	#[default]
+	Lenient,
+	Strict,
+}
+
+// What happens to chat messages and placements which can't be sent yet,
+// either because the client is disconnected or a cooldown hasn't passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OfflinePolicy {
+	// keep everything until it can be sent
+	#[default]
+	Queue,
+	// drop anything which has been waiting longer than this
+	Expire(Duration),
+	// drop anything queued while disconnected, and everything still queued
+	// when the connection is lost
+	Drop,
+}
+
+// What happens to messages received while the client is paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PausePolicy {
+	// keep them to be delivered on resume, with no limit on how many
+	#[default]
+	Buffer,
+	Drop,
+}
+
+// How requests the server turns away for being too frequent are retried.
+// Also limits how often board info is retried when connecting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	pub max_retries: usize,
+	// requests asked to wait longer than this fail straight away
+	pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_retries: 3,
+			max_delay: Duration::from_secs(60),
+		}
+	}
+}
+
+// How the client keeps track of the board's colors.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BoardStorage {
+	// the board is kept as an `AtomicBoard` which is updated in place, and
+	// `colors()` gives out a copy of it which is shared until the board next
+	// changes, so readers never see a partially applied update
+	#[default]
+	Snapshot,
+	// like `Snapshot`, but `colors()` copies the board every time, which can
+	// include part of a batch of updates, rather than keeping a copy around
+	Atomic,
+	// like `Atomic`, but the board and timestamps are kept in memory mapped
+	// files in this directory named after the canvas code, so they aren't
+	// held in memory and the files always hold the latest values
+	#[cfg(feature = "mmap")]
+	Mapped(PathBuf),
 }
 
 #[derive(Debug)]
 pub enum ClientBuildError {
 	MissingSite,
 	MissingEventHandler,
+	Tls(openssl::error::ErrorStack),
+	// `placement_rate_window` was zero, which has no average
+	ZeroPlacementRateWindow,
 }
 
 impl ClientBuidler {
@@ -47,23 +349,350 @@ impl ClientBuidler {
 		self
 	}
 
+	// Board buffers are downloaded from here instead of the site when set,
+	// for deployments which serve them from a CDN. The site is still used if
+	// this can't be reached.
+	pub fn buffer_base(mut self, base: Url) -> Self {
+		self.buffer_base = Some(base);
+		self
+	}
+
+	// Sends every request and the websocket over a unix socket instead of the
+	// network, for servers running locally. The site is still used for urls
+	// and the host header, but everything is sent unencrypted.
+	#[cfg(unix)]
+	pub fn unix_socket<P: Into<PathBuf>>(mut self, path: P) -> Self {
+		self.unix_socket = Some(path.into());
+		self
+	}
+
 	pub fn event_handler<H: EventHandler + 'static>(mut self, handler: H) -> Self {
-		self.event_handler = Some(Arc::new(handler));
+		self.event_handler = Some(Box::new(handler));
 		self
 	}
 
 	pub fn reconnect_time(mut self, time: Duration) -> Self {
-		self.reconnect_time = Some(time);
+		self.reconnect_policy = Some(Box::new(FixedDelay(time)));
+		self
+	}
+
+	// Replaces `reconnect_time` with a policy that can vary the delay or stop
+	// reconnecting altogether.
+	pub fn reconnect_policy<P: ReconnectPolicy + 'static>(mut self, policy: P) -> Self {
+		self.reconnect_policy = Some(Box::new(policy));
+		self
+	}
+
+	// Pixels received within the window are delivered together in a single
+	// call to `handle_board_update`.
+	pub fn board_update_window(mut self, window: Duration) -> Self {
+		self.board_update_window = Some(window);
+		self
+	}
+
+	// Pings the server whenever nothing has been received for the interval,
+	// and disconnects if the ping isn't answered within another interval.
+	pub fn heartbeat(mut self, interval: Duration) -> Self {
+		self.heartbeat = Some(interval);
+		self
+	}
+
+	// Drops all but the last pixel placed at each position from every call
+	// to `handle_board_update`. Most useful with a `board_update_window` of
+	// about a server tick, so that pixels fought over during a flood arrive
+	// as their final color only.
+	pub fn coalesce_pixels(mut self, coalesce: bool) -> Self {
+		self.coalesce_pixels = coalesce;
+		self
+	}
+
+	// How far back `placement_rate` looks, as the time constant of its
+	// moving average. Defaults to a minute.
+	pub fn placement_rate_window(mut self, window: Duration) -> Self {
+		self.placement_rate_window = Some(window);
+		self
+	}
+
+	// Also tracks the placement rate within a region, reported under the name.
+	pub fn rate_region<S: Into<String>>(mut self, name: S, region: Rect) -> Self {
+		self.rate_regions.push((name.into(), region));
+		self
+	}
+
+	// How many user counts to remember, 1024 by default.
+	pub fn user_count_history(mut self, samples: usize) -> Self {
+		self.user_count_history = Some(samples);
+		self
+	}
+
+	// Minimum time between sent chat messages, on top of any cooldown the
+	// server reports.
+	pub fn chat_interval(mut self, interval: Duration) -> Self {
+		self.chat_interval = Some(interval);
+		self
+	}
+
+	// Without a profile, `CompatProfile::auto` is used.
+	pub fn compat_profile(mut self, profile: CompatProfile) -> Self {
+		self.compat = Some(profile);
+		self
+	}
+
+	pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+		self.parse_mode = Some(mode);
+		self
+	}
+
+	pub fn session(mut self, session: Session) -> Self {
+		self.session = Some(session);
+		self
+	}
+
+	pub fn offline_policy(mut self, policy: OfflinePolicy) -> Self {
+		self.offline_policy = Some(policy);
+		self
+	}
+
+	// Has `Client::place` check the placemap and refuse pixels the server
+	// would reject instead of queueing them.
+	pub fn refuse_unplaceable(mut self, refuse: bool) -> Self {
+		self.refuse_unplaceable = refuse;
+		self
+	}
+
+	// Lets a reconnect go ahead with the board info from the previous
+	// connection if it can't be fetched again, rather than disconnecting.
+	pub fn stale_info(mut self, stale_info: bool) -> Self {
+		self.stale_info = stale_info;
+		self
+	}
+
+	// Cached values older than the age are still returned straight away, but
+	// reading them has `Client::revalidate` fetch them again in the
+	// background.
+	pub fn stale_while_revalidate(mut self, age: Duration) -> Self {
+		self.revalidate_after = Some(age);
+		self
+	}
+
+	// Reuses cached values and stats without asking the server again for as
+	// long as its Cache-Control or Expires headers allow, even when they are
+	// explicitly refreshed.
+	pub fn honor_cache_control(mut self, honor: bool) -> Self {
+		self.honor_cache_control = honor;
+		self
+	}
+
+	pub fn pause_policy(mut self, policy: PausePolicy) -> Self {
+		self.pause_policy = Some(policy);
+		self
+	}
+
+	// Applied to every REST request the client makes. Pass the same limiter to
+	// several clients to have them share a budget.
+	pub fn rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+		self.rate_limiter = Some(limiter);
+		self
+	}
+
+	// Caps how fast buffers are downloaded. Other requests are small enough
+	// that they aren't slowed down.
+	pub fn bandwidth(mut self, bandwidth: Arc<Bandwidth>) -> Self {
+		self.bandwidth = Some(bandwidth);
+		self
+	}
+
+	pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+		self.retry_policy = Some(policy);
+		self
+	}
+
+	// Board info and buffers are saved here so that they only need to be
+	// revalidated rather than downloaded again when the client restarts.
+	pub fn cache_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+		self.cache_dir = Some(dir.into());
+		self
+	}
+
+	pub fn board_storage(mut self, storage: BoardStorage) -> Self {
+		self.board_storage = Some(storage);
+		self
+	}
+
+	// How many lookup results are remembered, defaults to 256.
+	pub fn lookup_cache_size(mut self, size: usize) -> Self {
+		self.lookup_cache_size = Some(size);
+		self
+	}
+
+	// Used to replace the session when the server stops accepting it.
+	pub fn reauthenticator<R: Reauthenticator + 'static>(mut self, reauthenticator: R) -> Self {
+		self.reauthenticator = Some(Arc::new(reauthenticator));
+		self
+	}
+
+	// Used in place of the system's DNS for both REST requests and the
+	// websocket, see `StaticResolver` for pointing hosts at fixed addresses.
+	pub fn resolver<R: Resolver + 'static>(mut self, resolver: R) -> Self {
+		self.resolver = Some(Arc::new(resolver));
+		self
+	}
+
+	// Which IP versions to connect over, for hosts with broken IPv6 or IPv4.
+	pub fn address_family(mut self, family: AddressFamily) -> Self {
+		self.address_family = Some(family);
+		self
+	}
+
+	// Asks the server to compress websocket messages, which mostly helps with
+	// busy canvases on slow connections. Messages sent to the server are never
+	// compressed.
+	pub fn websocket_compression(mut self, compress: bool) -> Self {
+		self.websocket_compression = compress;
+		self
+	}
+
+	// Trusts the certificates in this PEM bundle in addition to the system's
+	// roots, for servers using an internal certificate authority.
+	pub fn add_root_certificate<P: Into<Vec<u8>>>(mut self, pem: P) -> Self {
+		self.tls.add_root_certificate(pem.into());
+		self
+	}
+
+	// Only accepts servers with this certificate somewhere in their chain.
+	// Pins can be combined, the server only has to match one of them.
+	pub fn pin_certificate(mut self, fingerprint: Fingerprint) -> Self {
+		self.tls.pin_certificate(fingerprint);
+		self
+	}
+
+	// Like `pin_certificate` but matches the public key, so the pin survives
+	// the certificate being reissued with the same key.
+	pub fn pin_public_key(mut self, fingerprint: Fingerprint) -> Self {
+		self.tls.pin_public_key(fingerprint);
+		self
+	}
+
+	// Can add headers to or otherwise adjust every request before it's sent,
+	// for things like proxy authentication or tracing.
+	pub fn request_interceptor<I: RequestInterceptor + 'static>(mut self, interceptor: I) -> Self {
+		self.request_interceptor = Some(Arc::new(interceptor));
+		self
+	}
+
+	pub fn chat_log(mut self, log: ChatLog) -> Self {
+		self.chat_log = Some(log);
+		self
+	}
+
+	pub fn event_log(mut self, log: EventLog) -> Self {
+		self.event_log = Some(log);
+		self
+	}
+
+	// Sends alerts, milestones, canvas resets and griefs to the sink, in
+	// addition to any other sinks added.
+	pub fn notification_sink<S: NotificationSink + 'static>(mut self, sink: S) -> Self {
+		self.notification_sinks.push(Box::new(sink));
+		self
+	}
+
+	// Skips verifying the server's certificate entirely, including any pins.
+	// Anyone able to intercept the connection can read and alter everything
+	// including the session token, so this is only meant for local
+	// development servers with self-signed certificates.
+	pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+		self.tls.danger_accept_invalid_certs(accept);
 		self
 	}
 
 	pub fn build(self) -> Result<Client, ClientBuildError> {
+		let site_base = self.site_base.ok_or(ClientBuildError::MissingSite)?;
+		let event_handler = self.event_handler.ok_or(ClientBuildError::MissingEventHandler)?;
+		let placement_rate_window = self.placement_rate_window.unwrap_or(Duration::from_secs(60));
+		if placement_rate_window.is_zero() {
+			return Err(ClientBuildError::ZeroPlacementRateWindow);
+		}
+
+		let mut http_tls = self.tls.connector().map_err(ClientBuildError::Tls)?;
+		http_tls.set_alpn_protos(b"\x02h2\x08http/1.1").map_err(ClientBuildError::Tls)?;
+		let resolver = self.resolver.unwrap_or_else(|| Arc::new(SystemResolver));
+		let address_family = self.address_family.unwrap_or_default();
+		let mut http = HttpConnector::new_with_resolver(HyperResolver {
+			resolver: resolver.clone(),
+			family: address_family,
+		});
+		http.enforce_http(false);
+		let https = HttpsConnector::with_connector(http, http_tls)
+			.map_err(ClientBuildError::Tls)?;
+		let connector = Connector::Tcp(https);
+		#[cfg(unix)]
+		let connector = match self.unix_socket.clone() {
+			Some(path) => Connector::Unix(path),
+			None => connector,
+		};
+
+		let compat = self.compat.unwrap_or_else(CompatProfile::auto);
+		let websocket_tls = self.tls.connector().map_err(ClientBuildError::Tls)?.build();
+
 		Ok(Client {
-			site_base: self.site_base.ok_or(ClientBuildError::MissingSite)?,
-			event_handler: self.event_handler.ok_or(ClientBuildError::MissingEventHandler)?,
-			http_client: hyper::Client::builder()
-				.build(hyper_openssl::HttpsConnector::new().unwrap()),
-			reconnect_time: self.reconnect_time.unwrap_or(Duration::from_secs(60)),
+			site_base,
+			buffer_base: self.buffer_base,
+			event_handler: ArcSwap::from_pointee(event_handler),
+			http_client: hyper::Client::builder().build(connector),
+			#[cfg(unix)]
+			unix_socket: self.unix_socket,
+			websocket_tls,
+			request_interceptor: self.request_interceptor,
+			chat_log: self.chat_log,
+			event_log: self.event_log,
+			notification_sinks: self.notification_sinks,
+			reconnect_policy: ArcSwap::from_pointee(self.reconnect_policy
+				.unwrap_or_else(|| Box::new(FixedDelay(Duration::from_secs(60))))),
+			board_update_window: RwLock::new(self.board_update_window),
+			heartbeat: RwLock::new(self.heartbeat),
+			coalesce_pixels: self.coalesce_pixels,
+			placement_rate: RateTracker::new(
+				placement_rate_window,
+				self.rate_regions,
+			),
+			user_counts: UserCountHistory::new(self.user_count_history.unwrap_or(1024)),
+			pending_pixels: Mutex::new(vec![]),
+			messages: broadcast::channel(1024).0,
+			cooldown: watch::channel(None).0,
+			writer: Mutex::new(None),
+			capabilities: std::sync::RwLock::new(compat.capabilities),
+			compat,
+			parse_mode: self.parse_mode.unwrap_or_default(),
+			session: RwLock::new(self.session),
+			reauthenticator: self.reauthenticator,
+			resolver,
+			address_family,
+			websocket_compression: self.websocket_compression,
+			reauthenticating: Mutex::new(()),
+			authenticated: AtomicBool::new(false),
+			user: RwLock::new(None),
+			reconnect_now: AtomicBool::new(false),
+			offline_policy: self.offline_policy.unwrap_or_default(),
+			refuse_unplaceable: self.refuse_unplaceable,
+			stale_info: self.stale_info,
+			revalidate_after: RwLock::new(self.revalidate_after),
+			honor_cache_control: self.honor_cache_control,
+			stats: Mutex::new(None),
+			stale: Mutex::new(HashSet::new()),
+			revalidate: Notify::new(),
+			pause_policy: self.pause_policy.unwrap_or_default(),
+			paused: Mutex::new(Paused::default()),
+			rate_limiter: self.rate_limiter,
+			bandwidth: self.bandwidth,
+			retry_policy: RwLock::new(self.retry_policy.unwrap_or_default()),
+			disk_cache: self.cache_dir.map(DiskCache::new),
+			board_storage: self.board_storage.unwrap_or_default(),
+			lookups: Mutex::new(LookupCache::new(self.lookup_cache_size.unwrap_or(256))),
+			placements: Mutex::new(PlacementQueue::default()),
+			chat: Mutex::new(ChatQueue::new(self.chat_interval.unwrap_or_default())),
+			queued: Notify::new(),
+			chat_drained: Notify::new(),
 			cache: ClientCache::default(),
 			connected: RwLock::new(false),
 		})
@@ -74,15 +703,298 @@ impl ClientBuidler {
 pub enum ConnectError {
 	InvalidSiteScheme(String),
 	WebsocketConnectFailed(Error),
+	Io(std::io::Error),
+	Tls(openssl::ssl::Error),
 	InfoFailed(RequestError),
 }
 
+// Errors which happen outside of any call made by the user and are instead
+// passed to `handle_error`.
+#[derive(Debug)]
+pub enum ClientError {
+	Connect(ConnectError),
+	Parse { packet: String, error: ParseError },
+	// writing to the chat or event log failed, the message is not retried
+	Log(std::io::Error),
+	Notify(NotifyError),
+	// loading or refreshing a cached value in the background failed
+	Refresh { kind: CacheKind, error: RequestError },
+}
+
+// Problems the client worked around, passed to `handle_warning`.
+#[derive(Debug)]
+pub enum ClientWarning {
+	// board info couldn't be fetched after connecting and will be tried
+	// again after the delay
+	InfoRetry { attempt: usize, error: RequestError, delay: Duration },
+	// board info couldn't be fetched at all, so the info from the previous
+	// connection is being used, see `ClientBuidler::stale_info`
+	StaleInfo(RequestError),
+}
+
+#[derive(Debug, Clone)]
+pub enum DisconnectReason {
+	// the server closed the connection, code and reason are empty if it
+	// didn't say why
+	Closed { code: Option<u16>, reason: String },
+	Transport(Arc<Error>),
+	// no userinfo arrived for the session, see `Reauthenticator`
+	SessionExpired,
+	// the connection ended without a close frame or error
+	Ended,
+	// a heartbeat ping went unanswered, see `ClientBuidler::heartbeat`
+	HeartbeatTimeout,
+}
+
+#[derive(Debug)]
+pub enum RenameError {
+	Request(RequestError),
+	Rejected(String),
+}
+
+#[derive(Debug)]
+pub enum PlaceError {
+	// the position is outside the board or masked off by the placemap, see
+	// `ClientBuidler::refuse_unplaceable`
+	Unplaceable(Pos),
+	// the placemap couldn't be loaded to check the position
+	Request(RequestError),
+}
+
+#[derive(Debug)]
+pub enum ChatError {
+	// the server has chat turned off, or its compatibility profile has no
+	// chat, see `Client::capabilities`
+	Disabled,
+}
+
+#[derive(Debug)]
+pub enum SendError {
+	NotConnected,
+	Websocket(Error),
+	Serialize(serde_json::Error),
+}
+
 #[derive(Debug)]
 pub enum RequestError {
 	Http(hyper::Error),
 	Buffer(hyper::Error),
 	ParseUTF8(std::str::Utf8Error),
 	ParseJSON(serde_json::Error),
+	Decompress(std::io::Error),
+	UnsupportedEncoding(String),
+	#[cfg(feature = "mmap")]
+	Storage(std::io::Error),
+	// a buffer didn't have one value for each pixel of the board
+	BufferSize { expected: usize, actual: usize },
+	// the server is refusing requests for at least this long
+	RateLimited(Duration),
+	// the server answered with an error status
+	Status(StatusCode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestErrorKind {
+	// the server couldn't be reached or the connection broke
+	Network,
+	// the server failed to handle the request
+	Server,
+	RateLimited,
+	// the session was refused or doesn't allow the request
+	Auth,
+	// the server considers the request itself wrong
+	Rejected,
+	// the response wasn't in the expected format
+	Deserialization,
+	// the local cache couldn't be used
+	Storage,
+}
+
+impl RequestError {
+	pub fn kind(&self) -> RequestErrorKind {
+		match self {
+			RequestError::Http(_) | RequestError::Buffer(_) => RequestErrorKind::Network,
+			RequestError::ParseUTF8(_)
+			| RequestError::ParseJSON(_)
+			| RequestError::Decompress(_)
+			| RequestError::UnsupportedEncoding(_)
+			| RequestError::BufferSize { .. } => RequestErrorKind::Deserialization,
+			#[cfg(feature = "mmap")]
+			RequestError::Storage(_) => RequestErrorKind::Storage,
+			RequestError::RateLimited(_) => RequestErrorKind::RateLimited,
+			RequestError::Status(status) => match *status {
+				StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => RequestErrorKind::Auth,
+				StatusCode::TOO_MANY_REQUESTS => RequestErrorKind::RateLimited,
+				status if status.is_server_error() => RequestErrorKind::Server,
+				_ => RequestErrorKind::Rejected,
+			},
+		}
+	}
+
+	// Whether the same request might succeed if made again later.
+	pub fn is_retryable(&self) -> bool {
+		matches!(
+			self.kind(),
+			RequestErrorKind::Network | RequestErrorKind::Server | RequestErrorKind::RateLimited,
+		)
+	}
+}
+
+// Keeps only the last pixel at each position, in the order those last
+// pixels arrived.
+fn coalesce(pixels: Vec<Pixel>) -> Vec<Pixel> {
+	let mut seen = HashSet::with_capacity(pixels.len());
+	let mut coalesced = pixels.into_iter().rev()
+		.filter(|pixel| seen.insert(pixel.position))
+		.collect::<Vec<_>>();
+	coalesced.reverse();
+	coalesced
+}
+
+// How long a response says to wait before trying again. Only 429s and server
+// errors which include a Retry-After header count as asking to wait.
+fn retry_after(response: &hyper::Response<hyper::Body>) -> Option<Option<Duration>> {
+	let status = response.status();
+	let header = response.headers().get(header::RETRY_AFTER)
+		.and_then(|value| value.to_str().ok());
+
+	let delay = header.and_then(|value| {
+		value.trim().parse::<u64>().ok()
+			.map(Duration::from_secs)
+			.or_else(|| {
+				let date = DateTime::parse_from_rfc2822(value).ok()?;
+				let delay = date.signed_duration_since(chrono::Utc::now());
+				Some(delay.to_std().unwrap_or_default())
+			})
+	});
+
+	match status {
+		StatusCode::TOO_MANY_REQUESTS => Some(delay),
+		status if status.is_server_error() && delay.is_some() => Some(delay),
+		_ => None,
+	}
+}
+
+// How long a response can be reused for according to its Cache-Control or
+// Expires header, `None` if it shouldn't be reused without asking the server.
+fn freshness(headers: &header::HeaderMap) -> Option<Duration> {
+	let text = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+	if let Some(cache_control) = text(header::CACHE_CONTROL) {
+		let mut max_age = None;
+		for directive in cache_control.split(',').map(str::trim) {
+			let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+			match name.to_ascii_lowercase().as_str() {
+				"no-store" | "no-cache" => return None,
+				"max-age" => max_age = value.trim_matches('"').parse::<u64>().ok(),
+				_ => (),
+			}
+		}
+
+		if let Some(max_age) = max_age {
+			// time already spent in caches along the way
+			let age = text(header::AGE)
+				.and_then(|age| age.trim().parse::<u64>().ok())
+				.unwrap_or_default();
+			return Some(Duration::from_secs(max_age.saturating_sub(age)));
+		}
+	}
+
+	let expires = DateTime::parse_from_rfc2822(text(header::EXPIRES)?).ok()?;
+	// measured against the server's clock if it gave the time
+	let date = text(header::DATE)
+		.and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+		.map(|date| date.with_timezone(&chrono::Utc))
+		.unwrap_or_else(chrono::Utc::now);
+	expires.signed_duration_since(date).to_std().ok()
+}
+
+fn decompress(
+	encoding: Option<&HeaderValue>,
+	body: Bytes,
+) -> Result<Bytes, RequestError> {
+	let mut decoded = vec![];
+
+	match encoding.map(HeaderValue::as_bytes) {
+		None | Some(b"identity") => return Ok(body),
+		Some(b"gzip") => GzDecoder::new(&body[..]).read_to_end(&mut decoded),
+		Some(b"deflate") => ZlibDecoder::new(&body[..]).read_to_end(&mut decoded),
+		Some(b"br") => BrotliDecoder::new(&body[..], 4096).read_to_end(&mut decoded),
+		Some(other) => {
+			let encoding = String::from_utf8_lossy(other).into_owned();
+			return Err(RequestError::UnsupportedEncoding(encoding));
+		},
+	}.map_err(RequestError::Decompress)?;
+
+	Ok(Bytes::from(decoded))
+}
+
+fn parse_json<T: DeserializeOwned>(body: Bytes) -> Result<T, RequestError> {
+	let text = std::str::from_utf8(&body)
+		.map_err(RequestError::ParseUTF8)?;
+	serde_json::from_str(text)
+		.map_err(RequestError::ParseJSON)
+}
+
+async fn read_throttled(mut body: hyper::Body, bandwidth: &Bandwidth) -> Result<Bytes, hyper::Error> {
+	let mut buffer = BytesMut::with_capacity(hyper::body::HttpBody::size_hint(&body).lower() as usize);
+	while let Some(chunk) = body.next().await {
+		let chunk = chunk?;
+		bandwidth.consume(chunk.len()).await;
+		buffer.extend_from_slice(&chunk);
+	}
+	Ok(buffer.freeze())
+}
+
+fn check_buffer_size(buffer: &[u8], info: &BoardInfo) -> Result<(), RequestError> {
+	let expected = info.width * info.height;
+	if buffer.len() == expected {
+		Ok(())
+	} else {
+		Err(RequestError::BufferSize { expected, actual: buffer.len() })
+	}
+}
+
+// board info fields which can be safely defaulted if a server doesn't send
+// them. Fields that anything is computed from, like `heatmapCooldown`, stay
+// required since a made up value would give wrong results or worse.
+fn info_defaults() -> serde_json::Value {
+	serde_json::json!({
+		"cooldownInfo": {
+			"type": "static",
+			"staticCooldownSeconds": 0,
+			"activityCooldown": { "steepness": 0 },
+		},
+		"captchaKey": "",
+		"maxStacked": 0,
+		"authServices": {},
+		"registrationEnabled": false,
+		"chatEnabled": false,
+		"chatRespectsCanvasBan": false,
+		"chatCharacterLimit": 0,
+		"chatBannerText": [],
+		"snipMode": false,
+		"customEmoji": [],
+		"corsBase": "",
+		"corsParam": "",
+		"chatRatelimitMessage": "",
+	})
+}
+
+fn parse_info_lenient(body: Bytes) -> Result<BoardInfo, RequestError> {
+	let text = std::str::from_utf8(&body)
+		.map_err(RequestError::ParseUTF8)?;
+	let mut value: serde_json::Value = serde_json::from_str(text)
+		.map_err(RequestError::ParseJSON)?;
+
+	if let (Some(info), serde_json::Value::Object(defaults)) = (value.as_object_mut(), info_defaults()) {
+		for (key, default) in defaults {
+			info.entry(key).or_insert(default);
+		}
+	}
+
+	serde_json::from_value(value)
+		.map_err(RequestError::ParseJSON)
 }
 
 fn deserialize_color_value<'de, D>(
@@ -184,6 +1096,13 @@ pub struct Color {
 	pub value: [u8; 3],
 }
 
+impl Color {
+	// A css-style hex string, such as "#ff0000".
+	pub fn hex(&self) -> String {
+		crate::messages::hex(self.value)
+	}
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all="lowercase")]
 pub enum CooldownType {
@@ -213,16 +1132,10 @@ pub struct AuthService {
 	pub registration_enabled: bool,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct Emoji {
-	pub emoji: String,
-	pub name: String,
-}
-
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all="camelCase")]
 pub struct BoardInfo {
-	pub canvas_code: String,
+	pub canvas_code: CanvasCode,
 	pub width: usize,
 	pub height: usize,
 	pub palette: Vec<Color>,
@@ -237,11 +1150,36 @@ pub struct BoardInfo {
 	pub chat_character_limit: usize,
 	pub chat_banner_text: Vec<String>,
 	pub snip_mode: bool,
-	pub custom_emoji: Vec<Emoji>,
+	pub custom_emoji: EmojiSet,
 	pub cors_base: String,
 	#[serde(with = "serde_with::rust::string_empty_as_none")]
 	pub cors_param: Option<String>,
 	pub chat_ratelimit_message: String,
+	// fields this crate doesn't know about, such as those added by forks
+	#[serde(flatten)]
+	pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl BoardInfo {
+	pub fn contains(&self, x: usize, y: usize) -> bool {
+		Pos::new(x, y).is_within(self.width, self.height)
+	}
+
+	pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+		if self.contains(x, y) {
+			Some(Pos::new(x, y).index(self.width))
+		} else {
+			None
+		}
+	}
+
+	pub fn coords(&self, index: usize) -> Option<Pos> {
+		if index < self.width * self.height {
+			Some(Pos::from_index(index, self.width))
+		} else {
+			None
+		}
+	}
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -330,22 +1268,171 @@ pub struct Stats {
 	pub generated_at: DateTime<chrono_tz::Tz>,
 }
 
-pub struct Client {
-	pub site_base: Url,
-	event_handler: Arc<dyn EventHandler>,
-	http_client: hyper::Client<HttpsConnector<HttpConnector>>,
-	reconnect_time: Duration,
-	cache: ClientCache,
-	connected: RwLock<bool>,
+// Which of the stats leaderboards to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Period {
+	AllTime,
+	Canvas,
+	Last15Minutes,
+	LastHour,
+	LastDay,
+	LastWeek,
 }
 
-impl std::fmt::Debug for Client {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Stats {
+	pub fn users(&self, period: Period) -> &[StatsUserEntry] {
+		match period {
+			Period::AllTime => &self.toplist.alltime,
+			Period::Canvas => &self.toplist.canvas,
+			Period::Last15Minutes => &self.breakdown.last_15m.users,
+			Period::LastHour => &self.breakdown.last_hour.users,
+			Period::LastDay => &self.breakdown.last_day.users,
+			Period::LastWeek => &self.breakdown.last_week.users,
+		}
+	}
+
+	// The first `n` places, best first.
+	pub fn top_n(&self, n: usize, period: Period) -> Vec<&StatsUserEntry> {
+		let mut users = self.users(period).iter().collect::<Vec<_>>();
+		users.sort_by_key(|user| user.place);
+		users.truncate(n);
+		users
+	}
+
+	// Usernames are compared case-insensitively.
+	pub fn user(&self, username: &str, period: Period) -> Option<&StatsUserEntry> {
+		self.users(period).iter()
+			.find(|user| user.username.eq_ignore_ascii_case(username))
+	}
+
+	// `None` for users who aren't on the leaderboard.
+	pub fn user_rank(&self, username: &str, period: Period) -> Option<usize> {
+		self.user(username, period).map(|user| user.place)
+	}
+
+	// Users whose name contains the query, ignoring case, best placed first.
+	pub fn search_users(&self, query: &str, period: Period) -> Vec<&StatsUserEntry> {
+		let query = query.to_lowercase();
+		let mut users = self.users(period).iter()
+			.filter(|user| user.username.to_lowercase().contains(&query))
+			.collect::<Vec<_>>();
+		users.sort_by_key(|user| user.place);
+		users
+	}
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Lookup {
+	pub id: u64,
+	#[serde(flatten)]
+	pub position: Pos,
+	// milliseconds since the unix epoch
+	pub time: u64,
+	pub username: Option<String>,
+	pub discord_name: Option<String>,
+	pub faction: Option<String>,
+	pub origin: Option<String>,
+	#[serde(rename = "pixel_count")]
+	pub pixel_count: Option<usize>,
+	#[serde(rename = "pixel_count_alltime")]
+	pub pixel_count_all_time: Option<usize>,
+	#[serde(flatten)]
+	pub extra: HashMap<String, serde_json::Value>,
+}
+
+pub struct Client {
+	pub site_base: Url,
+	buffer_base: Option<Url>,
+	// boxed since arc-swap can't hold unsized values
+	event_handler: ArcSwap<Box<dyn EventHandler>>,
+	http_client: hyper::Client<Connector>,
+	#[cfg(unix)]
+	unix_socket: Option<PathBuf>,
+	websocket_tls: SslConnector,
+	request_interceptor: Option<Arc<dyn RequestInterceptor>>,
+	chat_log: Option<ChatLog>,
+	event_log: Option<EventLog>,
+	notification_sinks: Vec<Box<dyn NotificationSink>>,
+	reconnect_policy: ArcSwap<Box<dyn ReconnectPolicy>>,
+	board_update_window: RwLock<Option<Duration>>,
+	heartbeat: RwLock<Option<Duration>>,
+	coalesce_pixels: bool,
+	placement_rate: RateTracker,
+	user_counts: UserCountHistory,
+	pending_pixels: Mutex<Vec<Pixel>>,
+	messages: broadcast::Sender<Message>,
+	cooldown: watch::Sender<Option<Instant>>,
+	writer: Mutex<Option<Writer>>,
+	compat: CompatProfile,
+	capabilities: std::sync::RwLock<Capabilities>,
+	parse_mode: ParseMode,
+	session: RwLock<Option<Session>>,
+	reauthenticator: Option<Arc<dyn Reauthenticator>>,
+	reauthenticating: Mutex<()>,
+	resolver: Arc<dyn Resolver>,
+	address_family: AddressFamily,
+	websocket_compression: bool,
+	authenticated: AtomicBool,
+	user: RwLock<Option<UserState>>,
+	reconnect_now: AtomicBool,
+	offline_policy: OfflinePolicy,
+	refuse_unplaceable: bool,
+	stale_info: bool,
+	revalidate_after: RwLock<Option<Duration>>,
+	// cached values read after going stale, waiting to be fetched again
+	stale: Mutex<HashSet<CacheKind>>,
+	revalidate: Notify,
+	honor_cache_control: bool,
+	// the last stats and until when the server said they could be reused
+	stats: Mutex<Option<(Stats, Instant)>>,
+	pause_policy: PausePolicy,
+	paused: Mutex<Paused>,
+	rate_limiter: Option<Arc<RateLimiter>>,
+	bandwidth: Option<Arc<Bandwidth>>,
+	retry_policy: RwLock<RetryPolicy>,
+	disk_cache: Option<DiskCache>,
+	board_storage: BoardStorage,
+	lookups: Mutex<LookupCache>,
+	placements: Mutex<PlacementQueue>,
+	chat: Mutex<ChatQueue>,
+	queued: Notify,
+	chat_drained: Notify,
+	cache: ClientCache,
+	connected: RwLock<bool>,
+}
+
+impl std::fmt::Debug for Client {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("Client").field("site_base", &self.site_base).finish()
 	}
 }
 
-enum BufferType {
+// how long to wait for userinfo before considering a session expired
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how long to wait for the server to confirm a rename
+const RENAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+// placemap value for pixels which are outside the shape of the canvas
+pub(crate) const UNPLACEABLE: u8 = 0xFF;
+
+// The values the client caches, see `Client::cache_age`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheKind {
+	Info,
+	Colors,
+	Board,
+	Initial,
+	Mask,
+	Heatmap,
+	Virginmap,
+	Timestamps,
+}
+
+// The per-pixel buffers the server provides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferType {
 	Colormap,
 	Placemap,
 	Heatmap,
@@ -370,337 +1457,1950 @@ impl Client {
 		ClientBuidler::default()
 	}
 
+	// The handler is replaced for any events after this, calls to the old
+	// handler which are already in progress still run to completion.
+	pub fn set_event_handler<H: EventHandler + 'static>(&self, handler: H) {
+		self.event_handler.store(Arc::new(Box::new(handler)));
+	}
+
+	// Takes effect from the next reconnect, any delay already being waited
+	// out is unaffected.
+	pub fn set_reconnect_policy<P: ReconnectPolicy + 'static>(&self, policy: P) {
+		self.reconnect_policy.store(Arc::new(Box::new(policy)));
+	}
+
+	pub fn set_reconnect_time(&self, time: Duration) {
+		self.set_reconnect_policy(FixedDelay(time));
+	}
+
+	// `None` delivers pixels as soon as they arrive.
+	pub async fn set_board_update_window(&self, window: Option<Duration>) {
+		*self.board_update_window.write().await = window;
+	}
+
+	// `None` stops pinging, changes apply from the next ping.
+	pub async fn set_heartbeat(&self, interval: Option<Duration>) {
+		*self.heartbeat.write().await = interval;
+	}
+
+	// `None` turns revalidation off, see
+	// `ClientBuidler::stale_while_revalidate`.
+	pub async fn set_stale_while_revalidate(&self, age: Option<Duration>) {
+		*self.revalidate_after.write().await = age;
+	}
+
+	pub async fn set_chat_interval(&self, interval: Duration) {
+		self.chat.lock().await.set_interval(interval);
+	}
+
+	pub async fn set_retry_policy(&self, policy: RetryPolicy) {
+		*self.retry_policy.write().await = policy;
+	}
+
+	fn event_handler(&self) -> Arc<Box<dyn EventHandler>> {
+		self.event_handler.load_full()
+	}
+
+	// Can be used to pause and resume recording.
+	pub fn event_log(&self) -> Option<&EventLog> {
+		self.event_log.as_ref()
+	}
+
 	pub async fn stats(&self)  -> Result<Stats, RequestError> {
+		let mut cached = self.stats.lock().await;
+		if let Some((stats, fresh_until)) = cached.as_ref() {
+			if Instant::now() < *fresh_until {
+				return Ok(stats.clone());
+			}
+		}
+
 		let location = self.site_base.join("stats/stats.json").unwrap();
-		let request = self.http_client.get(location.as_str().parse().unwrap()).await;
-
-		match request {
-			Ok(response) => {
-				hyper::body::to_bytes(response.into_body())
-					.await
-					.map_err(RequestError::Buffer)
-					.and_then(|body| {
-						let text = std::str::from_utf8(&body)
-							.map_err(RequestError::ParseUTF8)?;
-						serde_json::from_str(text)
-							.map_err(RequestError::ParseJSON)
-					})
-					
-			},
-			Err(e) => Err(RequestError::Http(e)),
-		}
-	}
-
-	pub async fn info(&self) -> Result<Arc<RwLock<BoardInfo>>, RequestError> {
-		let mut info = self.cache.info.lock().await;
-		if info.is_none() {
-			let location = self.site_base.join("info").unwrap();
-			let request = self.http_client.get(location.as_str().parse().unwrap()).await;
-
-			let info_data = match request {
-				Ok(response) => {
-					hyper::body::to_bytes(response.into_body())
-						.await
-						.map_err(RequestError::Buffer)
-						.and_then(|body| {
-							let text = std::str::from_utf8(&body)
-								.map_err(RequestError::ParseUTF8)?;
-							serde_json::from_str(text)
-								.map_err(RequestError::ParseJSON)
-						})
-						
-				},
-				Err(e) => Err(RequestError::Http(e)),
-			}?;
+		let mut validators = Validators::default();
+		let stats: Stats = self.request(location, &mut validators).await?
+			.map(parse_json)
+			.expect("Unconditional request was not modified")?;
+
+		*cached = validators.fresh_until
+			.filter(|_| self.honor_cache_control)
+			.map(|fresh_until| (stats.clone(), fresh_until));
+		Ok(stats)
+	}
 
-			*info = Some(Arc::new(RwLock::new(info_data)));
+	pub async fn users(&self) -> Result<usize, RequestError> {
+		#[derive(Deserialize)]
+		struct Users {
+			count: usize,
 		}
 
-		Ok(info.as_ref().unwrap().clone())
+		let location = self.site_base.join("users").unwrap();
+		self.get_json::<Users>(location).await.map(|users| users.count)
 	}
 
-	async fn fetch_buffer(&self, buffer: BufferType) -> Result<Vec<u8>, RequestError> {
-		let location = self.site_base.join(buffer.into()).unwrap();
-		let request = self.http_client.get(location.as_str().parse().unwrap()).await;
+	// Polls stats and calls `handle_milestone` whenever a new nth-pixel
+	// milestone has been reached. Milestones reached before the first poll
+	// are not reported.
+	pub async fn watch_milestones(&self, interval: Duration) {
+		let mut reached: Option<HashSet<u64>> = None;
+		let mut ticks = tokio::time::interval(interval);
 
-		match request {
-			Ok(response) => {
-				hyper::body::to_bytes(response.into_body()).await
-					.map(|body| body.to_vec())
-					.map_err(RequestError::Buffer)
-			},
-			Err(e) => Err(RequestError::Http(e)),
+		loop {
+			ticks.tick().await;
+
+			let stats = match self.stats().await {
+				Ok(stats) => stats,
+				Err(_) => continue,
+			};
+
+			let milestones = stats.general.nth_list.into_iter()
+				.filter(|milestone| milestone.user.is_some());
+			let mut now_reached = HashSet::new();
+
+			for milestone in milestones {
+				now_reached.insert(milestone.intval);
+				let is_new = reached.as_ref()
+					.is_some_and(|reached| !reached.contains(&milestone.intval));
+				if is_new {
+					self.notify(Notice::Milestone(milestone.clone())).await;
+					self.event_handler().handle_milestone(self, milestone).await;
+				}
+			}
+
+			reached = Some(now_reached);
 		}
 	}
 
-	pub async fn colors(&self) -> Result<Arc<RwLock<Vec<u8>>>, RequestError> {
-		let mut colors = self.cache.colors.lock().await;
-		if colors.is_none() {
-			let buffer = self.fetch_buffer(BufferType::Colormap).await?;
+	// A shareable link to a location on this client's canvas.
+	pub fn link_to<L: Into<CanvasLink>>(&self, link: L) -> Url {
+		link.into().to_url(&self.site_base)
+	}
+
+	pub async fn info(&self) -> Result<Arc<BoardInfo>, RequestError> {
+		self.load(&self.cache.info, "info", false, self.info_parser()).await
+	}
+
+	// If the board has changed size, loaded buffers are dropped and
+	// `handle_board_resize` is called.
+	pub async fn refresh_info(&self) -> Result<Arc<BoardInfo>, RequestError> {
+		let previous = self.cache.info.get();
+		let info = self.load(&self.cache.info, "info", true, self.info_parser()).await?;
 
-			*colors = Some(Arc::new(RwLock::new(buffer)));
+		if let Some(previous) = previous {
+			if (previous.width, previous.height) != (info.width, info.height) {
+				self.drop_buffers().await;
+			}
+			self.info_changed(&previous, &info).await;
 		}
 
-		Ok(colors.as_ref().unwrap().clone())
+		Ok(info)
+	}
+
+	// Fetches info for a new connection, retrying as often as the retry
+	// policy allows since the websocket is already open by this point.
+	async fn connect_info(
+		&self,
+		previous: Option<&Arc<BoardInfo>>,
+		previous_fetched_at: Option<Instant>,
+	) -> Result<Arc<BoardInfo>, RequestError> {
+		let mut attempt = 0;
+		loop {
+			let error = match self.info().await {
+				Ok(info) => return Ok(info),
+				Err(error) => error,
+			};
+
+			let policy = *self.retry_policy.read().await;
+			if attempt >= policy.max_retries || !error.is_retryable() {
+				return match previous.filter(|_| self.stale_info) {
+					Some(previous) => {
+						self.cache.info.set_arc(previous.clone(), previous_fetched_at);
+						self.event_handler().handle_warning(self, ClientWarning::StaleInfo(error)).await;
+						Ok(previous.clone())
+					},
+					None => Err(error),
+				};
+			}
+
+			attempt += 1;
+			let delay = Duration::from_secs(1 << attempt.min(6)).min(policy.max_delay);
+			let warning = ClientWarning::InfoRetry { attempt, error, delay };
+			self.event_handler().handle_warning(self, warning).await;
+			tokio::time::sleep(delay).await;
+		}
 	}
 
-	pub async fn initial_colors(&self) -> Result<Arc<RwLock<Vec<u8>>>, RequestError> {
-		let mut initial = self.cache.initial.lock().await;
-		if initial.is_none() {
-			let buffer = self.fetch_buffer(BufferType::InitialColormap).await?;
+	async fn info_changed(&self, previous: &BoardInfo, info: &BoardInfo) {
+		if (previous.width, previous.height) != (info.width, info.height) {
+			let old = (previous.width, previous.height);
+			let new = (info.width, info.height);
+			self.event_handler().handle_board_resize(self, old, new).await;
+		}
 
-			*initial = Some(Arc::new(RwLock::new(buffer)));
+		if previous.canvas_code != info.canvas_code {
+			self.notify(Notice::CanvasReset {
+				old_code: previous.canvas_code.clone(),
+				new_code: info.canvas_code.clone(),
+			}).await;
 		}
+	}
 
-		Ok(initial.as_ref().unwrap().clone())
+	async fn notify(&self, notice: Notice) {
+		for sink in &self.notification_sinks {
+			if let Err(error) = sink.notify(&notice).await {
+				self.event_handler().handle_error(self, ClientError::Notify(error)).await;
+			}
+		}
 	}
 
-	pub async fn mask(&self) -> Result<Arc<RwLock<Vec<u8>>>, RequestError> {
-		let mut mask = self.cache.mask.lock().await;
-		if mask.is_none() {
-			let buffer = self.fetch_buffer(BufferType::Placemap).await?;
+	// Drops every buffer sized for the old board so that they are fetched
+	// again the next time they are used. Anyone still holding an old buffer
+	// keeps it, but it no longer receives updates.
+	async fn drop_buffers(&self) {
+		let _guard = self.cache.lock(&[
+			Slot::Colors,
+			Slot::Board,
+			Slot::Initial,
+			Slot::Mask,
+			Slot::Heatmap,
+			Slot::Virginmap,
+			Slot::Timestamps,
+		]).await;
+		self.cache.colors.set(None);
+		self.cache.board.set(None);
+		self.cache.initial.set(None);
+		self.cache.mask.set(None);
+		self.cache.heatmap.set(None);
+		self.cache.virginmap.set(None);
+		self.cache.timestamps.set(None);
+	}
 
-			*mask = Some(Arc::new(RwLock::new(buffer)));
+	// Asks the server where to send the user to sign in with the given
+	// service. Once they have, pass the page they were sent back to on to
+	// `complete_sign_in`.
+	pub async fn sign_in_url(&self, service: &str) -> Result<Url, AuthError> {
+		let info = self.info().await?;
+		if !info.auth_services.contains_key(service) {
+			return Err(AuthError::UnknownService(service.to_owned()));
 		}
 
-		Ok(mask.as_ref().unwrap().clone())
+		let location = self.site_base.join(&format!("signin/{}", service)).unwrap();
+		let redirect = self.get_json::<SignInRedirect>(location).await?;
+		Url::parse(&redirect.url)
+			.map_err(|_| AuthError::UnknownService(service.to_owned()))
 	}
 
-	pub async fn timestamps(&self) -> Result<Arc<RwLock<Vec<u32>>>, RequestError> {
-		// we can generate a somewhat accurate timestamp buffer by merging the
-		// heatmap and the virginmap — the heatmap tells us somewhat accurate 
-		// times from the last few hours. Heatmap values of 0 can be interpreted
-		// as either untouched or as one higher than minimum based on virginmap.
+	// Exchanges the callback url the auth service redirected to for a
+	// session and starts using it.
+	pub async fn complete_sign_in(&self, callback: &Url) -> Result<SignIn, AuthError> {
+		let invalid = || AuthError::InvalidCallback(callback.clone());
+
+		let service = callback.path_segments()
+			.and_then(|mut segments| match (segments.next(), segments.next()) {
+				(Some("auth"), Some(service)) => Some(service.to_owned()),
+				_ => None,
+			})
+			.ok_or_else(invalid)?;
+
+		let mut location = self.site_base.join(&format!("auth/{}", service)).unwrap();
+		location.query_pairs_mut()
+			.extend_pairs(callback.query_pairs())
+			.append_pair("json", "1");
+
+		let response = self.get_json::<AuthResponse>(location).await?;
+		let sign_in = Result::<SignIn, AuthError>::from(response)?;
+		self.set_session(Some(sign_in.session.clone())).await;
+		Ok(sign_in)
+	}
 
-		let mut timestamps = self.cache.timestamps.lock().await;
-		if timestamps.is_none() {
-			let info = self.info().await?;
-			let info = info.read().await;
+	pub async fn session(&self) -> Option<Session> {
+		self.session.read().await.clone()
+	}
 
-			let now = SystemTime::now();
-			let mut created_at = self.cache.created_at.lock().await;
-			let canvas_start = created_at.get_or_insert_with(|| {
-				// We can compute the canvas start time as `now - heatmap_cooldown`.
-				// This is not entirely accurate, but it will suffice. 
-				// (We could be more accurate by accounting for the lowest
-				// non-virgin heatmap value, but scanning the entire heatmap and
-				// virginmap twice doesn't sound appealing.)
+	// The websocket is authenticated when it connects, so a new session only
+	// applies to it after the next reconnect.
+	pub async fn set_session(&self, session: Option<Session>) {
+		*self.session.write().await = session;
+	}
 
-				// +1 because we need to distinguish the oldest known pixels from
-				// virgin pixels
-				let canvas_age = u64::try_from(info.heatmap_cooldown).unwrap() + 1;
-				Arc::new(RwLock::new(now - Duration::from_secs(canvas_age)))
-			});
-			let canvas_start = canvas_start.read().await;
+	// Picks a new username after the server sends a rename request. Returns
+	// the name confirmed by the server's `RenameSuccess` message, or the
+	// requested name if that doesn't arrive shortly after.
+	pub async fn execute_rename(&self, new_name: &str) -> Result<String, RenameError> {
+		#[derive(Deserialize)]
+		struct RenameResponse {
+			success: bool,
+			details: Option<String>,
+		}
 
-			let heatmap = self.fetch_buffer(BufferType::Heatmap);
-			let virginmap = self.fetch_buffer(BufferType::Virginmap);
+		let confirmations = self.messages().filter_map(|message| async move {
+			match message {
+				Message::RenameSuccess { new_name } => Some(new_name),
+				_ => None,
+			}
+		});
+		futures_util::pin_mut!(confirmations);
 
-			let (heatmap, virginmap) = futures_util::try_join!(heatmap, virginmap)?;
+		let location = self.site_base.join("execNameChange").unwrap();
+		let response = self.post_form::<RenameResponse>(location, &[("newName", new_name)]).await
+			.map_err(RenameError::Request)?;
 
-			let timestamps_data = std::iter::zip(heatmap, virginmap)
-				.map(|(heat, virgin)| {
-					if virgin == 0 {
-						// pixel is non-virgin
-						let pixel_time = now - Duration::from_secs(u64::try_from(heat).unwrap());
-						u32::try_from(pixel_time.duration_since(*canvas_start).unwrap().as_secs())
-							.expect("Canvas is too old") // 136 years is a pretty long time
-					} else {
-						// pixel is virgin
-						0
-					}
-				})
-				.collect();
-			
-			*timestamps = Some(Arc::new(RwLock::new(timestamps_data)));
+		if !response.success {
+			let details = response.details.unwrap_or_default();
+			return Err(RenameError::Rejected(details));
 		}
 
-		Ok(timestamps.as_ref().unwrap().clone())
+		let confirmed = tokio::time::timeout(RENAME_TIMEOUT, confirmations.next()).await;
+		Ok(confirmed.ok().flatten().unwrap_or_else(|| new_name.to_owned()))
 	}
 
-	async fn update_buffers(&self, pixel: &Pixel) {
-		let info = self.info().await
-			.expect("Obtaining /info failed while updating buffers");
-		let info = info.read().await;
-		let colors = self.cache.colors.lock().await;
-		// NOTE: lock must happen in this order, otherwise we risk deadlock with
-		// timestamps().
-		let timestamps = self.cache.timestamps.lock().await;
-		let created_at = self.cache.created_at.lock().await;
+	// Asks the reauthenticator for a new session, returning whether one was
+	// obtained. If another task has already replaced the expired session in
+	// the meantime, that one is used instead.
+	async fn reauthenticate(&self, expired: Option<Session>) -> bool {
+		let reauthenticator = match self.reauthenticator.as_ref() {
+			Some(reauthenticator) => reauthenticator,
+			None => return false,
+		};
 
-		let index = pixel.y * info.width + pixel.x;
+		let _guard = self.reauthenticating.lock().await;
+		if self.session().await != expired {
+			return true;
+		}
 
-		if let Some(buffer) = colors.as_ref() {
-			let mut buffer = buffer.write().await;
-			buffer[index] = pixel.color;
+		match reauthenticator.reauthenticate(self, expired).await {
+			Some(session) => {
+				self.set_session(Some(session)).await;
+				true
+			},
+			None => false,
 		}
-		drop(colors);
+	}
 
-		if let Some(buffer) = timestamps.as_ref() {
-			let mut buffer = buffer.write().await;
-			let now = SystemTime::now();
-			let canvas_epoch = created_at
-				.as_ref()
-				.expect("Timestamps exist but canvas has no start time")
-				.read()
-				.await;
-			let timestamp = now.duration_since(*canvas_epoch).unwrap().as_secs();
-			buffer[index] = u32::try_from(timestamp).expect("Canvas is too old");
+	fn info_parser(&self) -> fn(Bytes) -> Result<BoardInfo, RequestError> {
+		match self.parse_mode {
+			ParseMode::Lenient => parse_info_lenient,
+			ParseMode::Strict => parse_json,
 		}
-		drop(timestamps);
 	}
 
-	async fn clear_cache(&self) {
-		let mut info = self.cache.info.lock().await;
-		let mut colors = self.cache.colors.lock().await;
-		let mut initial = self.cache.initial.lock().await;
-		let mut mask = self.cache.mask.lock().await;
-		let mut timestamps = self.cache.timestamps.lock().await;
-		let mut created_at = self.cache.created_at.lock().await;
-		*info = None;
-		*colors = None;
-		*initial = None;
-		*mask = None;
-		*timestamps = None;
-		*created_at = None;
-	}
-
-	async fn connect(&self) -> Result<(), ConnectError> {
-		let mut ws_url = self.site_base.join("ws").unwrap();
+	async fn request(
+		&self,
+		location: Url,
+		validators: &mut Validators,
+	) -> Result<Option<Bytes>, RequestError> {
+		let mut reauthenticated = false;
+		let mut retries = 0;
+
+		let response = loop {
+			let session = self.session().await;
+			let mut request = hyper::Request::get(location.as_str())
+				.header(header::ACCEPT_ENCODING, "gzip, deflate, br");
+
+			if let Some(session) = session.as_ref() {
+				request = request.header(header::COOKIE, session.cookie());
+			}
 
-		match ws_url.scheme() {
-			"http" => ws_url.set_scheme("ws").unwrap(),
-			"https" => ws_url.set_scheme("wss").unwrap(),
-			s => return Err(ConnectError::InvalidSiteScheme(s.to_owned())),
+			if let Some(etag) = validators.etag.as_ref() {
+				request = request.header(header::IF_NONE_MATCH, etag);
+			}
+			if let Some(last_modified) = validators.last_modified.as_ref() {
+				request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+			}
+
+			let request = self.intercept(request.body(hyper::Body::empty()).unwrap());
+			self.rate_limit(&location).await;
+			let response = self.http_client.request(request).await
+				.map_err(RequestError::Http)?;
+
+			// retry once with a new session if the current one was refused
+			let refused = response.status() == StatusCode::UNAUTHORIZED;
+			if refused && !reauthenticated && self.reauthenticate(session).await {
+				reauthenticated = true;
+				continue;
+			}
+
+			if let Some(delay) = retry_after(&response) {
+				let delay = delay.unwrap_or(Duration::from_secs(1 << retries.min(6)));
+				self.event_handler().handle_rate_limited(self, location.clone(), delay).await;
+
+				let policy = *self.retry_policy.read().await;
+				if retries >= policy.max_retries || delay > policy.max_delay {
+					return Err(RequestError::RateLimited(delay));
+				}
+
+				retries += 1;
+				tokio::time::sleep(delay).await;
+				continue;
+			}
+
+			break response;
 		};
 
-		let (ws_stream, _) = connect_async(ws_url)
-			.await
-			.map_err(ConnectError::WebsocketConnectFailed)?;
+		validators.fresh_until = freshness(response.headers())
+			.map(|fresh_for| Instant::now() + fresh_for);
+		let conditional = validators.etag.is_some() || validators.last_modified.is_some();
 
-		// cached is cleared once we reconnect rather than after we disconnect
-		// so that cached data can still be used, even if it is stale.
-		self.clear_cache().await;
-		*self.connected.write().await = true;
-			
-		// TODO: ping
-		let (write, read) = ws_stream.split();
+		if conditional && response.status() == StatusCode::NOT_MODIFIED {
+			return Ok(None);
+		}
 
-		self.info().await.map_err(ConnectError::InfoFailed)?;
-		self.event_handler.handle_ready(self, ).await;
+		if !response.status().is_success() {
+			return Err(RequestError::Status(response.status()));
+		}
 
-		let stream = read.for_each(|message| async {
-			if let Ok(message) = message {
-				let text = message.into_text().expect("Websocket didn't send text");
+		let headers = response.headers();
+		validators.etag = headers.get(header::ETAG).cloned();
+		validators.last_modified = headers.get(header::LAST_MODIFIED).cloned();
 
-				match serde_json::from_str::<Message>(&text) {
-					Ok(Message::Acknowledge { ack_for, x, y }) => {
-						self.event_handler.handle_acknowledge(self, ack_for, x, y).await
-					},
-					Ok(Message::AdminPlacementOverrides { placement_overrides }) => {
-						self.event_handler.handle_overrides(self, placement_overrides).await
-					},
-					Ok(Message::Alert { sender, message }) => {
-						self.event_handler.handle_alert(self, sender, message).await
-					},
-					Ok(Message::CanUndo { time }) => {
-						self.event_handler.handle_can_undo(self, time).await
-					},
-					Ok(Message::CaptchaRequired) => {
-						self.event_handler.handle_captcha_required(self, ).await
-					},
-					Ok(Message::CaptchaStatus { success }) => {
-						self.event_handler.handle_captcha_status(self, success).await
-					},
-					Ok(Message::ChatBan { permanent, reason, expiry }) => {
-						self.event_handler.handle_chatban(self, permanent, reason, expiry).await
-					},
-					Ok(Message::ChatBanState { permanent, reason, expiry }) => {
-						self.event_handler.handle_chatban_state(self, permanent, reason, expiry).await
-					},
-					Ok(Message::ChatHistory { messages }) => {
-						self.event_handler.handle_chat_history(self, messages).await
-					},
-					Ok(Message::ChatLookup { target, history, chatbans }) => {
-						self.event_handler.handle_chat_lookup(self, target, history, chatbans).await
-					},
-					Ok(Message::ChatMessage { message }) => {
-						self.event_handler.handle_chat_message(self, message).await
-					},
-					Ok(Message::ChatPurge { target, initiator, amount, reason, announce }) => {
-						self.event_handler.handle_chat_purge(self, target, initiator, amount, reason, announce).await
-					},
-					Ok(Message::ChatPurgeSpecific { target, initiator, IDs, reason, announce }) => {
-						self.event_handler.handle_chat_purge_specific(self, target, initiator, IDs, reason, announce).await
-					},
-					Ok(Message::ChatUserUpdate { who, updates }) => {
-						self.event_handler.handle_chat_user_update(self, who, updates).await
-					},
-					Ok(Message::Cooldown { wait }) => {
-						self.event_handler.handle_cooldown(self, wait).await
-					},
-					Ok(Message::FactionClear { fid }) => {
-						self.event_handler.handle_faction_clear(self, fid).await
-					},
-					Ok(Message::FactionUpdate { faction }) => {
-						self.event_handler.handle_faction_update(self, faction).await
-					},
-					Ok(Message::MessageCooldown { diff, message }) => {
-						self.event_handler.handle_message_cooldown(self, diff, message).await
-					},
-					Ok(Message::Notification { notification }) => {
-						self.event_handler.handle_notification(self, notification).await
-					},
-					Ok(Message::Pixel { pixels }) => {
-						for pixel in &pixels {
-							self.update_buffers(pixel).await;
-						}
-						self.event_handler.handle_board_update(self, pixels).await
-					},
-					Ok(Message::PixelCounts { pixel_count, pixel_count_all_time }) => {
-						self.event_handler.handle_pixel_counts(self, pixel_count, pixel_count_all_time).await
-					},
-					Ok(Message::Pixels { count, cause }) => {
-						self.event_handler.handle_pixels_available(self, count, cause).await
-					},
-					Ok(Message::ReceivedReport { report_id, report_type }) => {
-						self.event_handler.handle_received_report(self, report_id, report_type).await
-					},
-					Ok(Message::Rename { requested }) => {
-						self.event_handler.handle_rename(self, requested).await
-					},
-					Ok(Message::RenameSuccess { new_name }) => {
-						self.event_handler.handle_rename_success(self, new_name).await
-					},
-					Ok(Message::Userinfo { username, roles, pixel_count, pixel_count_all_time, banned, ban_expiry, ban_reason, method, placement_overrides, chat_banned, chatban_reason, chatban_is_perma, chatban_expiry, rename_requested, discord_name, chat_name_color }) => {
-						self.event_handler.handle_user_info(self, username, roles, pixel_count, pixel_count_all_time, banned, ban_expiry, ban_reason, method, placement_overrides, chat_banned, chatban_reason, chatban_is_perma, chatban_expiry, rename_requested, discord_name, chat_name_color).await
+		let encoding = headers.get(header::CONTENT_ENCODING).cloned();
+		let bandwidth = self.bandwidth.as_ref()
+			.filter(|_| RequestClass::of(&location) == RequestClass::Buffer);
+		let body = match bandwidth {
+			Some(bandwidth) => read_throttled(response.into_body(), bandwidth).await,
+			None => hyper::body::to_bytes(response.into_body()).await,
+		}.map_err(RequestError::Buffer)?;
+
+		decompress(encoding.as_ref(), body).map(Some)
+	}
+
+	async fn get(&self, location: Url) -> Result<Bytes, RequestError> {
+		self.request(location, &mut Validators::default()).await
+			.map(|body| body.expect("Unconditional request was not modified"))
+	}
+
+	async fn get_json<T: DeserializeOwned>(&self, location: Url) -> Result<T, RequestError> {
+		self.get(location).await.and_then(parse_json)
+	}
+
+	fn intercept<B>(&self, request: http::Request<B>) -> http::Request<B> {
+		match self.request_interceptor.as_ref() {
+			Some(interceptor) => {
+				let (mut parts, body) = request.into_parts();
+				interceptor.intercept(&mut parts);
+				http::Request::from_parts(parts, body)
+			},
+			None => request,
+		}
+	}
+
+	async fn rate_limit(&self, location: &Url) {
+		if let Some(limiter) = self.rate_limiter.as_ref() {
+			limiter.acquire(RequestClass::of(location)).await;
+		}
+	}
+
+	// How many more requests of this class can be made right now, or `None`
+	// if there is no limit on it.
+	pub async fn request_budget(&self, class: RequestClass) -> Option<usize> {
+		match self.rate_limiter.as_ref() {
+			Some(limiter) => limiter.budget(class).await,
+			None => None,
+		}
+	}
+
+	async fn post_form<T: DeserializeOwned>(
+		&self,
+		location: Url,
+		form: &[(&str, &str)],
+	) -> Result<T, RequestError> {
+		let body = url::form_urlencoded::Serializer::new(String::new())
+			.extend_pairs(form)
+			.finish();
+
+		let mut reauthenticated = false;
+		let response = loop {
+			let session = self.session().await;
+			let mut request = hyper::Request::post(location.as_str())
+				.header(header::ACCEPT_ENCODING, "gzip, deflate, br")
+				.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+
+			if let Some(session) = session.as_ref() {
+				request = request.header(header::COOKIE, session.cookie());
+			}
+
+			let request = self.intercept(request.body(hyper::Body::from(body.clone())).unwrap());
+			self.rate_limit(&location).await;
+			let response = self.http_client.request(request).await
+				.map_err(RequestError::Http)?;
+
+			// a refused session means the form wasn't acted on, so like
+			// `request` it's sent once more with a new one
+			let refused = response.status() == StatusCode::UNAUTHORIZED;
+			if refused && !reauthenticated && self.reauthenticate(session).await {
+				reauthenticated = true;
+				continue;
+			}
+
+			break response;
+		};
+
+		// forms aren't necessarily safe to send twice, so these aren't retried
+		if let Some(delay) = retry_after(&response) {
+			let delay = delay.unwrap_or_default();
+			self.event_handler().handle_rate_limited(self, location, delay).await;
+			return Err(RequestError::RateLimited(delay));
+		}
+
+		if !response.status().is_success() {
+			return Err(RequestError::Status(response.status()));
+		}
+
+		let encoding = response.headers().get(header::CONTENT_ENCODING).cloned();
+		let body = hyper::body::to_bytes(response.into_body()).await
+			.map_err(RequestError::Buffer)?;
+
+		decompress(encoding.as_ref(), body).and_then(parse_json)
+	}
+
+	async fn mark_stale<T>(&self, cache: &Cache<T>) {
+		let revalidate_after = *self.revalidate_after.read().await;
+		let stale = revalidate_after
+			.zip(cache.fetched_at())
+			.is_some_and(|(max_age, fetched_at)| fetched_at.elapsed() > max_age);
+
+		if let Some(kind) = cache.slot.kind().filter(|_| stale) {
+			if self.stale.lock().await.insert(kind) {
+				self.revalidate.notify_one();
+			}
+		}
+	}
+
+	// Fetches a cached value again, replacing it if the server has a newer
+	// version.
+	pub async fn refresh(&self, kind: CacheKind) -> Result<(), RequestError> {
+		match kind {
+			CacheKind::Info => self.refresh_info().await.map(drop),
+			CacheKind::Colors => self.refresh_colors().await.map(drop),
+			CacheKind::Board => self.refresh_board().await.map(drop),
+			CacheKind::Initial => self.refresh_initial_colors().await.map(drop),
+			CacheKind::Mask => self.refresh_mask().await.map(drop),
+			CacheKind::Heatmap => self.refresh_heatmap().await.map(drop),
+			CacheKind::Virginmap => self.refresh_virginmap().await.map(drop),
+			CacheKind::Timestamps => self.refresh_timestamps().await.map(drop),
+		}
+	}
+
+	// Refreshes cached values which were read after going stale, calling
+	// `handle_cache_refreshed` as each one lands. Only does anything with
+	// `ClientBuidler::stale_while_revalidate`; run it alongside the
+	// connection.
+	pub async fn revalidate(&self) {
+		loop {
+			self.revalidate.notified().await;
+			let stale = std::mem::take(&mut *self.stale.lock().await);
+			for kind in stale {
+				match self.refresh(kind).await {
+					Ok(()) => self.event_handler().handle_cache_refreshed(self, kind).await,
+					Err(error) => {
+						let error = ClientError::Refresh { kind, error };
+						self.event_handler().handle_error(self, error).await
 					},
-					Ok(Message::Users { count }) => {
-						self.event_handler.handle_user_count(self, count).await
-					}
-					Err(_) => {
-						self.event_handler.handle_unknown(self, text).await
+				}
+			}
+		}
+	}
+
+	// Fetches the heatmap and virginmap again every interval, along with the
+	// timestamps made from them. Only the live colors are kept up to date by
+	// the connection, so without this they drift further from the server
+	// the longer the client runs. Buffers which haven't been loaded yet are
+	// left alone.
+	pub async fn refresh_buffers(&self, interval: Duration) {
+		let mut ticks = tokio::time::interval(interval);
+		// the first tick completes straight away
+		ticks.tick().await;
+
+		loop {
+			ticks.tick().await;
+
+			for kind in [CacheKind::Heatmap, CacheKind::Virginmap, CacheKind::Timestamps] {
+				if self.cache_age(kind).is_none() {
+					continue;
+				}
+
+				match self.refresh(kind).await {
+					Ok(()) => self.event_handler().handle_cache_refreshed(self, kind).await,
+					Err(error) => {
+						let error = ClientError::Refresh { kind, error };
+						self.event_handler().handle_error(self, error).await
 					},
 				}
 			}
-		});
+		}
+	}
 
-		stream.await;
+	// Populates the cache entry if it's empty. When `refresh` is set, an
+	// existing entry is revalidated and only replaced if the server reports
+	// that it has changed.
+	async fn load<T>(
+		&self,
+		cache: &Cache<T>,
+		endpoint: &'static str,
+		refresh: bool,
+		parse: impl FnOnce(Bytes) -> Result<T, RequestError>,
+	) -> Result<Arc<T>, RequestError> {
+		if let (Some(cached), false) = (cache.get(), refresh) {
+			self.mark_stale(cache).await;
+			return Ok(cached);
+		}
 
-		*self.connected.write().await = false;
-		self.event_handler.handle_disconnect(self).await;
+		let _guard = self.cache.lock(&[cache.slot]).await;
+		let cached = cache.get();
+		if cached.is_none() || refresh {
+			let disk_path = self.disk_path(endpoint).await;
+			let mut disk_entry = None;
+
+			let mut validators = if cached.is_some() {
+				self.cache.validators.lock().await
+					.get(endpoint)
+					.cloned()
+					.unwrap_or_default()
+			} else if let (Some(disk), Some(path)) = (self.disk_cache.as_ref(), disk_path.as_ref()) {
+				disk_entry = disk.read(path).await;
+				disk_entry.as_ref()
+					.map(|entry| Validators {
+						etag: entry.etag.clone(),
+						last_modified: entry.last_modified.clone(),
+						fresh_until: None,
+					})
+					.unwrap_or_default()
+			} else {
+				Validators::default()
+			};
 
-		Ok(())
+			let fresh = self.honor_cache_control && cached.is_some()
+				&& validators.fresh_until.is_some_and(|fresh_until| Instant::now() < fresh_until);
+			if fresh {
+				return Ok(cached.unwrap());
+			}
+
+			let body = match self.request_endpoint(endpoint, &mut validators).await? {
+				Some(body) => {
+					if let (Some(disk), Some(path)) = (self.disk_cache.as_ref(), disk_path.as_ref()) {
+						let entry = DiskEntry {
+							body: body.clone(),
+							etag: validators.etag.clone(),
+							last_modified: validators.last_modified.clone(),
+						};
+						disk.write(path, &entry).await;
+					}
+					Some(body)
+				},
+				None => disk_entry.map(|entry| entry.body),
+			};
+
+			if let Some(body) = body {
+				cache.set(Some(parse(body)?));
+			} else {
+				cache.touch();
+			}
+			self.cache.validators.lock().await.insert(endpoint, validators);
+		}
+
+		Ok(cache.get().unwrap())
 	}
 
-	pub async fn start(&self) {
-		loop {
-			self.connect().await;
-			tokio::time::sleep(self.reconnect_time).await;
+	// Who last placed a pixel and when. Results are cached until the pixel
+	// changes.
+	pub async fn lookup(&self, position: Pos) -> Result<Option<Lookup>, RequestError> {
+		if let Some(lookup) = self.lookups.lock().await.get(position) {
+			return Ok(lookup);
 		}
+
+		let mut location = self.site_base.join("lookup").unwrap();
+		location.query_pairs_mut()
+			.append_pair("x", &position.x.to_string())
+			.append_pair("y", &position.y.to_string());
+
+		let lookup = self.get_json::<Option<Lookup>>(location).await?;
+		self.lookups.lock().await.insert(position, lookup.clone());
+		Ok(lookup)
 	}
 
-	pub async fn is_connected(&self) -> bool {
-		*self.connected.read().await
+	// Buffers are requested from the buffer base when there is one, falling
+	// back on the site if that fails.
+	async fn request_endpoint(
+		&self,
+		endpoint: &'static str,
+		validators: &mut Validators,
+	) -> Result<Option<Bytes>, RequestError> {
+		if let (Some(base), false) = (self.buffer_base.as_ref(), endpoint == "info") {
+			let location = base.join(endpoint).unwrap();
+			if let Ok(body) = self.request(location, validators).await {
+				return Ok(body);
+			}
+		}
+
+		let location = self.site_base.join(endpoint).unwrap();
+		self.request(location, validators).await
+	}
+
+	async fn disk_path(&self, endpoint: &'static str) -> Option<PathBuf> {
+		let disk = self.disk_cache.as_ref()?;
+		if endpoint == "info" {
+			Some(disk.path(None, endpoint))
+		} else {
+			// only the cached info is used since fetching it here would recurse
+			let info = self.cache.info.get()?;
+			Some(disk.path(Some(&info.canvas_code), endpoint))
+		}
+	}
+
+	// Like `load`, but checks that the buffer matches the board's size. If it
+	// doesn't, board info is refreshed in case the board has changed size and
+	// the buffer is fetched once more.
+	async fn load_buffer<T, F>(
+		&self,
+		cache: &Cache<T>,
+		buffer: BufferType,
+		refresh: bool,
+		convert: F,
+	) -> Result<Arc<T>, RequestError>
+	where F: Fn(Bytes, &BoardInfo) -> Result<T, RequestError> {
+		let info = self.info().await?;
+		let parse = |body: Bytes| {
+			check_buffer_size(&body, &info)?;
+			convert(body, &info)
+		};
+
+		match self.load(cache, buffer.into(), refresh, parse).await {
+			Err(RequestError::BufferSize { .. }) => {
+				let info = self.refresh_info().await?;
+				let parse = |body: Bytes| {
+					check_buffer_size(&body, &info)?;
+					convert(body, &info)
+				};
+				self.load(cache, buffer.into(), true, parse).await
+			},
+			result => result,
+		}
+	}
+
+	async fn fetch_buffer(&self, buffer: BufferType, info: &BoardInfo) -> Result<Bytes, RequestError> {
+		let body = self.request_endpoint(buffer.into(), &mut Validators::default()).await?
+			.expect("Unconditional request was not modified");
+		check_buffer_size(&body, info)?;
+		Ok(body)
+	}
+
+	// How long ago a cached value was fetched or last confirmed current by
+	// the server, `None` if it isn't cached. The colors, board and
+	// timestamps are kept up to date by placements, so their age matters less.
+	pub fn cache_age(&self, kind: CacheKind) -> Option<Duration> {
+		let fetched_at = match kind {
+			CacheKind::Info => self.cache.info.fetched_at(),
+			// the colors are always a copy of the board
+			CacheKind::Colors => self.cache.board.fetched_at(),
+			CacheKind::Board => self.cache.board.fetched_at(),
+			CacheKind::Initial => self.cache.initial.fetched_at(),
+			CacheKind::Mask => self.cache.mask.fetched_at(),
+			CacheKind::Heatmap => self.cache.heatmap.fetched_at(),
+			CacheKind::Virginmap => self.cache.virginmap.fetched_at(),
+			CacheKind::Timestamps => self.cache.timestamps.fetched_at(),
+		};
+		fetched_at.map(|fetched_at| fetched_at.elapsed())
+	}
+
+	// Downloads a buffer as the server has it right now, bypassing the cache
+	// entirely. Nothing cached is updated by it.
+	pub async fn fetch_raw_buffer(&self, buffer: BufferType) -> Result<Bytes, RequestError> {
+		let info = self.info().await?;
+		self.fetch_buffer(buffer, &info).await
+	}
+
+	// A copy of `board()`. With snapshot storage the copy is shared until the
+	// board next changes, otherwise it's a fresh copy each time.
+	pub async fn colors(&self) -> Result<Arc<BytesMut>, RequestError> {
+		let board = self.board().await?;
+		Ok(self.copy_board(&board).await)
+	}
+
+	async fn copy_board(&self, board: &Arc<AtomicBoard>) -> Arc<BytesMut> {
+		if self.board_storage != BoardStorage::Snapshot {
+			return Arc::new(board.copy());
+		}
+
+		if let Some(snapshot) = self.cache.colors.get().filter(|snapshot| snapshot.is_current(board)) {
+			return snapshot.colors.clone();
+		}
+
+		// batches of pixels are applied while holding this, so the copy has
+		// either all or none of each batch
+		let _guard = self.cache.lock(&[Slot::Colors]).await;
+		if let Some(snapshot) = self.cache.colors.get().filter(|snapshot| snapshot.is_current(board)) {
+			return snapshot.colors.clone();
+		}
+
+		let snapshot = ColorSnapshot {
+			board: Arc::downgrade(board),
+			version: board.version(),
+			colors: Arc::new(board.copy()),
+		};
+		let colors = snapshot.colors.clone();
+		self.cache.colors.set(Some(snapshot));
+		colors
+	}
+
+	// The palette index at a position and the palette entry it refers to, if
+	// any. `None` if the position is outside the board.
+	pub async fn color_at(&self, x: usize, y: usize) -> Result<Option<(u8, Option<Color>)>, RequestError> {
+		let color = match self.board_storage {
+			BoardStorage::Snapshot => {
+				let view = self.board_view().await?;
+				view.get(x, y)
+					.map(|index| (index, view.info().palette.get(usize::from(index)).cloned()))
+			},
+			_ => {
+				let info = self.info().await?;
+				let board = self.board().await?;
+				board.get(x, y)
+					.map(|index| (index, info.palette.get(usize::from(index)).cloned()))
+			},
+		};
+
+		Ok(color)
+	}
+
+	// A hash of the current colors, the same for any two boards with the
+	// same colors no matter how they're stored.
+	pub async fn board_hash(&self) -> Result<u64, RequestError> {
+		match self.board_storage {
+			BoardStorage::Snapshot => Ok(self.board_view().await?.hash()),
+			_ => Ok(self.board().await?.hash()),
+		}
+	}
+
+	// A read-only view of the board with its dimensions, see `BoardView`.
+	pub async fn board_view(&self) -> Result<BoardView, RequestError> {
+		let (info, colors) = self.matching_colors().await?;
+		Ok(BoardView::new(info, colors))
+	}
+
+	// An owned copy of the colors along with the board info they belong to.
+	// Unlike calling `info` and `colors` separately, the two can't come from
+	// either side of a resize.
+	pub async fn snapshot_colors(&self) -> Result<Snapshot, RequestError> {
+		let (info, colors) = self.matching_colors().await?;
+		Ok(Snapshot {
+			canvas_code: info.canvas_code.clone(),
+			width: info.width,
+			height: info.height,
+			palette: info.palette.clone(),
+			colors: Bytes::copy_from_slice(&colors),
+			taken_at: SystemTime::now(),
+		})
+	}
+
+	// The colors along with the info they belong to. The info may change while
+	// the colors are loading, in which case both are refreshed once rather than
+	// retrying for as long as they keep disagreeing.
+	async fn matching_colors(&self) -> Result<(Arc<BoardInfo>, Arc<BytesMut>), RequestError> {
+		let info = self.info().await?;
+		let colors = self.colors().await?;
+		if colors.len() == info.width * info.height {
+			return Ok((info, colors));
+		}
+
+		let info = self.refresh_info().await?;
+		let colors = self.refresh_colors().await?;
+		let expected = info.width * info.height;
+		if colors.len() == expected {
+			Ok((info, colors))
+		} else {
+			Err(RequestError::BufferSize { expected, actual: colors.len() })
+		}
+	}
+
+	pub async fn refresh_colors(&self) -> Result<Arc<BytesMut>, RequestError> {
+		let board = self.refresh_board().await?;
+		Ok(self.copy_board(&board).await)
+	}
+
+	// The board colors, kept up to date in place as pixels are placed.
+	pub async fn board(&self) -> Result<Arc<AtomicBoard>, RequestError> {
+		self.load_board(false).await
+	}
+
+	pub async fn refresh_board(&self) -> Result<Arc<AtomicBoard>, RequestError> {
+		self.load_board(true).await
+	}
+
+	async fn load_board(&self, refresh: bool) -> Result<Arc<AtomicBoard>, RequestError> {
+		#[cfg(feature = "mmap")]
+		if let BoardStorage::Mapped(dir) = &self.board_storage {
+			let parse = |body: Bytes, info: &BoardInfo| {
+				let path = dir.join(path_component(&info.canvas_code)).with_extension("colors");
+				AtomicBoard::mapped(info.width, info.height, &body, &path)
+					.map_err(RequestError::Storage)
+			};
+			return self.load_buffer(&self.cache.board, BufferType::Colormap, refresh, parse).await;
+		}
+
+		let parse = |body: Bytes, info: &BoardInfo| {
+			Ok(AtomicBoard::new(info.width, info.height, &body))
+		};
+		self.load_buffer(&self.cache.board, BufferType::Colormap, refresh, parse).await
+	}
+
+	pub async fn initial_colors(&self) -> Result<Arc<Bytes>, RequestError> {
+		self.load_buffer(&self.cache.initial, BufferType::InitialColormap, false, |body, _| Ok(body)).await
+	}
+
+	pub async fn refresh_initial_colors(&self) -> Result<Arc<Bytes>, RequestError> {
+		self.load_buffer(&self.cache.initial, BufferType::InitialColormap, true, |body, _| Ok(body)).await
+	}
+
+	pub async fn mask(&self) -> Result<Arc<Bytes>, RequestError> {
+		self.load_buffer(&self.cache.mask, BufferType::Placemap, false, |body, _| Ok(body)).await
+	}
+
+	pub async fn refresh_mask(&self) -> Result<Arc<Bytes>, RequestError> {
+		self.load_buffer(&self.cache.mask, BufferType::Placemap, true, |body, _| Ok(body)).await
+	}
+
+	// How many pixels of the board currently have each palette color,
+	// indexed by palette index.
+	pub async fn color_distribution(&self) -> Result<Vec<u64>, RequestError> {
+		let info = self.info().await?;
+		let colors = self.colors().await?;
+
+		let mut distribution = vec![0; info.palette.len()];
+		for &color in colors.iter() {
+			if let Some(count) = distribution.get_mut(usize::from(color)) {
+				*count += 1;
+			}
+		}
+
+		Ok(distribution)
+	}
+
+	pub async fn is_placeable(&self, x: usize, y: usize) -> Result<bool, RequestError> {
+		let info = self.info().await?;
+		let index = match info.index(x, y) {
+			Some(index) => index,
+			None => return Ok(false),
+		};
+
+		let mask = self.mask().await?;
+		let placeable = mask
+			.get(index)
+			.map(|&value| value != UNPLACEABLE)
+			.unwrap_or(false);
+
+		Ok(placeable)
+	}
+
+	// How much of the canvas has been placed on and how much differs from
+	// its initial state.
+	pub async fn canvas_activity_summary(&self) -> Result<CanvasActivity, RequestError> {
+		let mask = self.mask().await?;
+		let virginmap = self.virginmap().await?;
+		let colors = self.board_view().await?;
+		let initial = self.initial_colors().await?;
+		Ok(CanvasActivity::measure(&mask, &virginmap, colors.as_bytes(), &initial))
+	}
+
+	// The board and every buffer that goes with it, see `Dump`.
+	pub async fn dump(&self) -> Result<Dump, RequestError> {
+		let snapshot = self.snapshot_colors().await?;
+		let initial = self.initial_colors().await?;
+		let mask = self.mask().await?;
+		let virginmap = self.virginmap().await?;
+		let heatmap = self.heatmap().await?;
+
+		Ok(Dump {
+			canvas_code: snapshot.canvas_code,
+			width: snapshot.width,
+			height: snapshot.height,
+			palette: snapshot.palette,
+			taken_at: snapshot.taken_at,
+			colors: snapshot.colors,
+			initial: Some(Bytes::clone(&initial)),
+			mask: Some(Bytes::clone(&mask)),
+			virginmap: Some(Bytes::copy_from_slice(&virginmap)),
+			heatmap: Some(Bytes::copy_from_slice(&heatmap)),
+		})
+	}
+
+	pub async fn export_dump<W: std::io::Write>(&self, writer: W) -> Result<(), DumpError> {
+		self.dump().await.map_err(DumpError::Request)?.export(writer)
+	}
+
+	// Pixels whose current color differs from the start of the canvas.
+	pub async fn changed_since_start(&self) -> Result<Vec<Pixel>, RequestError> {
+		let info = self.info().await?;
+		let colors = self.colors().await?;
+		let initial = self.initial_colors().await?;
+
+		Ok(scan::changed(&colors, &initial, info.width))
+	}
+
+	pub async fn heatmap(&self) -> Result<Arc<BytesMut>, RequestError> {
+		self.load_buffer(&self.cache.heatmap, BufferType::Heatmap, false, |body, _| Ok(body.into())).await
+	}
+
+	pub async fn refresh_heatmap(&self) -> Result<Arc<BytesMut>, RequestError> {
+		self.load_buffer(&self.cache.heatmap, BufferType::Heatmap, true, |body, _| Ok(body.into())).await
+	}
+
+	pub async fn virginmap(&self) -> Result<Arc<BytesMut>, RequestError> {
+		self.load_buffer(&self.cache.virginmap, BufferType::Virginmap, false, |body, _| Ok(body.into())).await
+	}
+
+	pub async fn refresh_virginmap(&self) -> Result<Arc<BytesMut>, RequestError> {
+		self.load_buffer(&self.cache.virginmap, BufferType::Virginmap, true, |body, _| Ok(body.into())).await
+	}
+
+	pub async fn timestamps(&self) -> Result<Arc<Timestamps>, RequestError> {
+		self.load_timestamps(false).await
+	}
+
+	pub async fn refresh_timestamps(&self) -> Result<Arc<Timestamps>, RequestError> {
+		self.load_timestamps(true).await
+	}
+
+	async fn load_timestamps(&self, refresh: bool) -> Result<Arc<Timestamps>, RequestError> {
+		// we can generate a somewhat accurate timestamp buffer by merging the
+		// heatmap and the virginmap — the heatmap tells us somewhat accurate 
+		// times from the last few hours. Heatmap values of 0 can be interpreted
+		// as either untouched or as one higher than minimum based on virginmap.
+
+		let cached = self.cache.timestamps.get();
+		if let (Some(timestamps), false) = (cached.clone(), refresh) {
+			self.mark_stale(&self.cache.timestamps).await;
+			return Ok(timestamps);
+		}
+
+		let info = self.info().await?;
+		let _guard = self.cache.lock(&[Slot::Timestamps, Slot::CreatedAt]).await;
+		// unless another task got there first while we were waiting
+		let current = self.cache.timestamps.get();
+		let replaced = match (&cached, &current) {
+			(Some(cached), Some(current)) => !Arc::ptr_eq(cached, current),
+			_ => current.is_some(),
+		};
+		if !replaced {
+			let now = SystemTime::now();
+			let canvas_start = self.cache.created_at.get().unwrap_or_else(|| {
+				// We can compute the canvas start time as `now - heatmap_cooldown`.
+				// This is not entirely accurate, but it will suffice. 
+				// (We could be more accurate by accounting for the lowest
+				// non-virgin heatmap value, but scanning the entire heatmap and
+				// virginmap twice doesn't sound appealing.)
+
+				// +1 because we need to distinguish the oldest known pixels from
+				// virgin pixels
+				let canvas_age = u64::try_from(info.heatmap_cooldown).unwrap() + 1;
+				let canvas_start = Arc::new(now - Duration::from_secs(canvas_age));
+				self.cache.created_at.value.store(Some(canvas_start.clone()));
+				canvas_start
+			});
+
+			let heatmap = self.fetch_buffer(BufferType::Heatmap, &info);
+			let virginmap = self.fetch_buffer(BufferType::Virginmap, &info);
+
+			let (heatmap, virginmap) = futures_util::try_join!(heatmap, virginmap)?;
+
+			let timestamps_data = scan::timestamps(&heatmap, &virginmap, now, *canvas_start);
+			let timestamps = match &self.board_storage {
+				#[cfg(feature = "mmap")]
+				BoardStorage::Mapped(dir) => {
+					let path = dir.join(path_component(&info.canvas_code)).with_extension("timestamps");
+					Timestamps::mapped(&timestamps_data, &path)
+						.map_err(RequestError::Storage)?
+				},
+				_ => Timestamps::new(timestamps_data),
+			};
+
+			self.cache.timestamps.set(Some(timestamps));
+		}
+
+		Ok(self.cache.timestamps.get().unwrap())
+	}
+
+	pub async fn template_progress(&self, template: &Template) -> Result<TemplateProgress, RequestError> {
+		let board = self.board_view().await?;
+		let virginmap = self.virginmap().await?;
+		Ok(TemplateTracker::new(template.clone(), &board, &virginmap).progress())
+	}
+
+	// Downloads the image a template link refers to and positions it on this
+	// client's palette.
+	#[cfg(feature = "image")]
+	pub async fn load_template(&self, link: &TemplateLink) -> Result<Template, TemplateLoadError> {
+		let info = self.info().await.map_err(TemplateLoadError::Request)?;
+		let image = self.get(link.image.clone()).await.map_err(TemplateLoadError::Request)?;
+		link.to_template(&image, &info.palette)
+	}
+
+	// Queues every placement needed to complete the template, in the order
+	// given by `Template::mismatches`. Returns the number of placements
+	// waiting to be sent.
+	pub async fn place_template(&self, template: &Template) -> Result<usize, RequestError> {
+		let board = self.board_view().await?;
+		let mut queued = self.placement_queue_len().await;
+		for pixel in template.mismatches(&board) {
+			queued = match self.place(pixel).await {
+				Ok(queued) => queued,
+				Err(PlaceError::Unplaceable(_)) => continue,
+				Err(PlaceError::Request(error)) => return Err(error),
+			};
+		}
+		Ok(queued)
+	}
+
+	// Follows a template, calling `handle_template_progress` each time its
+	// completion passes one of the thresholds and `handle_grief` whenever a
+	// correct pixel is replaced. Progress is counted from placements, so
+	// anything which changes while disconnected isn't noticed.
+	pub async fn watch_template(&self, template: Template, watch: TemplateWatch) -> Result<(), RequestError> {
+		// subscribed first so that nothing is missed while the board loads
+		let messages = self.messages();
+		futures_util::pin_mut!(messages);
+
+		let board = self.board_view().await?;
+		let virginmap = self.virginmap().await?;
+		let mut tracker = TemplateTracker::new(template, &board, &virginmap);
+
+		while let Some(message) = messages.next().await {
+			if let Message::Pixel { pixels } = message {
+				let before = tracker.progress();
+				let griefs = pixels.iter()
+					.filter_map(|pixel| tracker.apply(pixel))
+					.collect::<Vec<_>>();
+
+				for mut grief in griefs {
+					if watch.lookup_griefers {
+						grief.lookup = self.lookup(grief.position).await.ok().flatten();
+					}
+					let template = tracker.template().name.clone();
+					self.notify(Notice::Grief { template, grief: grief.clone() }).await;
+					self.event_handler().handle_grief(self, tracker.template(), grief).await;
+				}
+
+				let after = tracker.progress();
+				if before.crosses(&after, &watch.thresholds) {
+					self.event_handler().handle_template_progress(self, tracker.template(), after).await;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	// Roughly when the pixel at a position was last placed, going by the
+	// timestamps buffer. `None` for pixels which have never been placed and
+	// positions outside the board.
+	pub async fn pixel_placed_at(&self, x: usize, y: usize) -> Result<Option<SystemTime>, RequestError> {
+		let info = self.info().await?;
+		let timestamps = self.timestamps().await?;
+		let canvas_epoch = match self.cache.created_at.get() {
+			Some(canvas_epoch) => canvas_epoch,
+			None => return Ok(None),
+		};
+
+		let position = Pos::new(x, y);
+		if !position.is_within(info.width, info.height) {
+			return Ok(None);
+		}
+
+		let placed_at = timestamps.get(position.index(info.width))
+			.filter(|&timestamp| timestamp != 0)
+			.map(|timestamp| *canvas_epoch + Duration::from_secs(u64::from(timestamp)));
+
+		Ok(placed_at)
+	}
+
+	// How long ago the pixel at a position was placed, see `pixel_placed_at`.
+	pub async fn pixel_age(&self, x: usize, y: usize) -> Result<Option<Duration>, RequestError> {
+		let placed_at = self.pixel_placed_at(x, y).await?;
+		Ok(placed_at.map(|placed_at| placed_at.elapsed().unwrap_or_default()))
+	}
+
+	// Applies a whole batch of pixels, taking each lock only once.
+	async fn update_buffers(&self, pixels: &[Pixel]) {
+		let info = match self.info().await {
+			Ok(info) => info,
+			Err(error) => return self.pixels_dropped(error).await,
+		};
+		let _guard = self.cache.lock(&[
+			Slot::Colors,
+			Slot::Board,
+			Slot::Heatmap,
+			Slot::Virginmap,
+			Slot::Timestamps,
+			Slot::CreatedAt,
+		]).await;
+
+		let updates = pixels.iter()
+			.filter_map(|pixel| {
+				info.index(pixel.position.x, pixel.position.y)
+					.map(|index| (index, pixel.color))
+			})
+			.collect::<Vec<_>>();
+
+		if updates.is_empty() {
+			return;
+		}
+
+		if let Some(board) = self.cache.board.get() {
+			board.update(&updates);
+		}
+
+		self.cache.heatmap.modify(|buffer| {
+			for &(index, _) in &updates {
+				if let Some(value) = buffer.get_mut(index) {
+					*value = u8::MAX;
+				}
+			}
+		});
+
+		self.cache.virginmap.modify(|buffer| {
+			for &(index, _) in &updates {
+				if let Some(value) = buffer.get_mut(index) {
+					*value = 0;
+				}
+			}
+		});
+
+		if let Some(canvas_epoch) = self.cache.created_at.get() {
+			let now = SystemTime::now();
+			let timestamp = now.duration_since(*canvas_epoch).unwrap().as_secs();
+			let timestamp = u32::try_from(timestamp).expect("Canvas is too old");
+			if let Some(timestamps) = self.cache.timestamps.get() {
+				for &(index, _) in &updates {
+					timestamps.set_index(index, timestamp);
+				}
+			}
+		}
+	}
+
+	// Pixels which couldn't be applied for lack of board info leave the cached
+	// buffers out of date, so they're dropped to be fetched again when needed.
+	async fn pixels_dropped(&self, error: RequestError) {
+		self.clear_cache().await;
+		let error = ClientError::Refresh { kind: CacheKind::Info, error };
+		self.event_handler().handle_error(self, error).await
+	}
+
+	async fn clear_cache(&self) {
+		self.lookups.lock().await.clear();
+		let _guard = self.cache.lock(&[
+			Slot::Info,
+			Slot::Colors,
+			Slot::Board,
+			Slot::Initial,
+			Slot::Mask,
+			Slot::Heatmap,
+			Slot::Virginmap,
+			Slot::Timestamps,
+			Slot::CreatedAt,
+		]).await;
+		let mut validators = self.cache.validators.lock().await;
+		self.cache.info.set(None);
+		self.cache.colors.set(None);
+		self.cache.board.set(None);
+		self.cache.initial.set(None);
+		self.cache.mask.set(None);
+		self.cache.heatmap.set(None);
+		self.cache.virginmap.set(None);
+		self.cache.timestamps.set(None);
+		self.cache.created_at.set(None);
+		validators.clear();
+	}
+
+	async fn handle_message(&self, message: WebsocketMessage) {
+		let text = match message {
+			WebsocketMessage::Binary(data) if self.capabilities().binary_pixels => {
+				let info = match self.info().await {
+					Ok(info) => info,
+					Err(error) => return self.pixels_dropped(error).await,
+				};
+				return match Message::parse_packed(&data, info.width) {
+					Ok(message) => self.dispatch_message(message).await,
+					Err(error) => {
+						let packet = String::from_utf8_lossy(&data).into_owned();
+						let error = ClientError::Parse { packet, error };
+						self.event_handler().handle_error(self, error).await
+					},
+				};
+			},
+			WebsocketMessage::Ping(_) | WebsocketMessage::Pong(_) => return,
+			WebsocketMessage::Binary(data) => {
+				let packet = String::from_utf8_lossy(&data).into_owned();
+				return self.event_handler().handle_unknown(self, packet).await;
+			},
+			message => message.into_text().expect("Websocket didn't send text"),
+		};
+
+		self.event_handler().handle_raw(self, &text).await;
+
+		let message = match Message::parse(&text) {
+			Ok(message) => message,
+			Err(error) if self.parse_mode == ParseMode::Strict => {
+				let error = ClientError::Parse { packet: text, error };
+				return self.event_handler().handle_error(self, error).await;
+			},
+			Err(_) if !self.compat.is_standard() => match self.compat.parse(&text) {
+				Ok(message) => message,
+				Err(_) => return self.event_handler().handle_unknown(self, text).await,
+			},
+			Err(_) => return self.event_handler().handle_unknown(self, text).await,
+		};
+
+		self.dispatch_message(message).await
+	}
+
+	async fn dispatch_message(&self, message: Message) {
+		if let Message::Userinfo { .. } = message {
+			self.authenticated.store(true, Ordering::SeqCst);
+		}
+
+		if let Some(log) = self.chat_log.as_ref().filter(|_| ChatLog::records(&message)) {
+			if let Err(error) = log.record(&message).await {
+				self.event_handler().handle_error(self, ClientError::Log(error)).await;
+			}
+		}
+
+		if let Some(log) = self.event_log.as_ref() {
+			if let Err(error) = log.record(&message).await {
+				self.event_handler().handle_error(self, ClientError::Log(error)).await;
+			}
+		}
+
+		if self.messages.receiver_count() > 0 {
+			let _ = self.messages.send(message.clone());
+		}
+
+		match &message {
+			Message::Acknowledge { .. } => {
+				self.placements.lock().await.answered();
+			},
+			Message::Userinfo { .. } => {
+				*self.user.write().await = UserState::from_message(&message);
+			},
+			Message::Cooldown { wait } => {
+				let ready_at = Instant::now() + *wait;
+				self.cooldown.send_replace(Some(ready_at));
+				self.placements.lock().await.answered();
+			},
+			Message::Alert { sender, message } => {
+				let (sender, message) = (sender.clone(), message.clone());
+				self.notify(Notice::Alert { sender, message }).await;
+			},
+			Message::MessageCooldown { diff, message } => {
+				let cooldown = Duration::from_secs(u64::try_from(*diff).unwrap());
+				let info = self.cache.info.get();
+				let ratelimit_message = info.as_ref().map(|info| info.chat_ratelimit_message.as_str());
+				self.chat.lock().await.rate_limited(cooldown, message, ratelimit_message);
+			},
+			Message::ChatMessage { message } => {
+				let own = self.user.read().await.as_ref()
+					.is_some_and(|user| user.username == message.author);
+				if own {
+					self.chat.lock().await.confirmed(&message.message_raw);
+				}
+			},
+			Message::Users { count } => {
+				self.user_counts.record(*count);
+			},
+			Message::Pixel { pixels } => {
+				self.placement_rate.record(pixels);
+				self.update_buffers(pixels).await;
+				let mut lookups = self.lookups.lock().await;
+				for pixel in pixels {
+					lookups.invalidate(pixel.position);
+				}
+			},
+			_ => {
+				if let Some(user) = self.user.write().await.as_mut() {
+					user.update(&message);
+				}
+			},
+		}
+
+		let mut paused = self.paused.lock().await;
+		if paused.paused || !paused.buffered.is_empty() {
+			if !paused.paused || self.pause_policy == PausePolicy::Buffer {
+				paused.buffered.push_back(message);
+			}
+			return;
+		}
+		drop(paused);
+
+		self.deliver_message(message).await
+	}
+
+	async fn deliver_message(&self, message: Message) {
+		match message {
+			Message::Acknowledge { ack_for, x, y } => {
+				self.event_handler().handle_acknowledge(self, ack_for, Pos::new(x, y)).await
+			},
+			Message::AdminPlacementOverrides { placement_overrides } => {
+				self.event_handler().handle_overrides(self, placement_overrides).await
+			},
+			Message::Alert { sender, message } => {
+				self.event_handler().handle_alert(self, sender, message).await
+			},
+			Message::CanUndo { time } => {
+				self.event_handler().handle_can_undo(self, time).await
+			},
+			Message::CaptchaRequired => {
+				self.event_handler().handle_captcha_required(self, ).await
+			},
+			Message::CaptchaStatus { success } => {
+				self.event_handler().handle_captcha_status(self, success).await
+			},
+			Message::ChatBan { permanent, reason, expiry } => {
+				self.event_handler().handle_chatban(self, permanent, reason, expiry).await
+			},
+			Message::ChatBanState { permanent, reason, expiry } => {
+				self.event_handler().handle_chatban_state(self, permanent, reason, expiry).await
+			},
+			Message::ChatHistory { messages } => {
+				self.event_handler().handle_chat_history(self, messages).await
+			},
+			Message::ChatLookup { target, history, chatbans } => {
+				self.event_handler().handle_chat_lookup(self, target, history, chatbans).await
+			},
+			Message::ChatMessage { message } => {
+				self.event_handler().handle_chat_message(self, message).await
+			},
+			Message::ChatPurge { target, initiator, amount, reason, announce } => {
+				self.event_handler().handle_chat_purge(self, target, initiator, amount, reason, announce).await
+			},
+			Message::ChatPurgeSpecific { target, initiator, IDs, reason, announce } => {
+				self.event_handler().handle_chat_purge_specific(self, target, initiator, IDs, reason, announce).await
+			},
+			Message::ChatUserUpdate { who, updates } => {
+				self.event_handler().handle_chat_user_update(self, who, updates).await
+			},
+			Message::Cooldown { wait } => {
+				self.event_handler().handle_cooldown(self, wait).await
+			},
+			Message::FactionClear { fid } => {
+				self.event_handler().handle_faction_clear(self, fid).await
+			},
+			Message::FactionUpdate { faction } => {
+				self.event_handler().handle_faction_update(self, faction).await
+			},
+			Message::MessageCooldown { diff, message } => {
+				self.event_handler().handle_message_cooldown(self, diff, message).await
+			},
+			Message::Notification { notification } => {
+				self.event_handler().handle_notification(self, notification).await
+			},
+			Message::Pixel { pixels } => {
+				if self.board_update_window.read().await.is_some() {
+					self.pending_pixels.lock().await.extend(pixels);
+				} else {
+					self.deliver_board_update(pixels).await
+				}
+			},
+			Message::PixelCounts { pixel_count, pixel_count_all_time } => {
+				self.event_handler().handle_pixel_counts(self, pixel_count, pixel_count_all_time).await
+			},
+			Message::Pixels { count, cause } => {
+				self.event_handler().handle_pixels_available(self, count, cause).await
+			},
+			Message::ReceivedReport { report_id, report_type } => {
+				self.event_handler().handle_received_report(self, report_id, report_type).await
+			},
+			Message::Rename { requested } => {
+				self.event_handler().handle_rename(self, requested).await
+			},
+			Message::RenameSuccess { new_name } => {
+				self.event_handler().handle_rename_success(self, new_name).await
+			},
+			Message::Userinfo { username, roles, pixel_count, pixel_count_all_time, banned, ban_expiry, ban_reason, method, placement_overrides, chat_banned, chatban_reason, chatban_is_perma, chatban_expiry, rename_requested, discord_name, chat_name_color } => {
+				self.event_handler().handle_user_info(self, username, roles, pixel_count, pixel_count_all_time, banned, ban_expiry, ban_reason, method, placement_overrides, chat_banned, chatban_reason, chatban_is_perma, chatban_expiry, rename_requested, discord_name, chat_name_color).await
+			},
+			Message::Users { count } => {
+				self.event_handler().handle_user_count(self, count).await
+			},
+		}
+	}
+
+	// Stops calling message handlers until `resume` is called. The connection
+	// stays open and the cached board keeps being updated, messages received
+	// in the meantime are kept or dropped according to the pause policy.
+	pub async fn pause(&self) {
+		self.paused.lock().await.paused = true;
+	}
+
+	// Delivers any messages kept while paused, in the order they arrived,
+	// before returning to handling messages as they come in.
+	pub async fn resume(&self) {
+		self.paused.lock().await.paused = false;
+
+		loop {
+			let message = {
+				let mut paused = self.paused.lock().await;
+				if paused.paused {
+					return;
+				}
+				match paused.buffered.pop_front() {
+					Some(message) => message,
+					None => return,
+				}
+			};
+
+			self.deliver_message(message).await;
+		}
+	}
+
+	pub async fn is_paused(&self) -> bool {
+		self.paused.lock().await.paused
+	}
+
+	// Sends a message straight away, bypassing the chat and placement queues
+	// and their rate limits.
+	pub async fn send(&self, message: ClientMessage) -> Result<(), SendError> {
+		let text = serde_json::to_string(&message).map_err(SendError::Serialize)?;
+		self.send_raw(text).await
+	}
+
+	// Sends a text frame as is, for messages the client doesn't otherwise
+	// support. Nothing is queued, so this fails while disconnected.
+	pub async fn send_raw<S: Into<String>>(&self, text: S) -> Result<(), SendError> {
+		let mut writer = self.writer.lock().await;
+		let writer = writer.as_mut().ok_or(SendError::NotConnected)?;
+		writer.send(WebsocketMessage::Text(text.into())).await
+			.map_err(SendError::Websocket)
+	}
+
+	async fn send_queued_chat(&self) {
+		let message = self.chat.lock().await.take();
+		if let Some(message) = message {
+			let packet = ClientMessage::ChatMessage { message: message.clone() };
+
+			if self.send(packet).await.is_err() {
+				self.chat.lock().await.unsend(message);
+			}
+		}
+
+		if self.chat.lock().await.is_empty() {
+			self.chat_drained.notify_waiters();
+		}
+	}
+
+	async fn send_queued_placement(&self) {
+		let pixel = self.placements.lock().await.take();
+		if let Some(pixel) = pixel {
+			if self.send(ClientMessage::Place(pixel.clone())).await.is_err() {
+				self.placements.lock().await.unsend(pixel);
+			}
+		}
+	}
+
+	async fn expire_queued(&self) {
+		if let OfflinePolicy::Expire(max_age) = self.offline_policy {
+			self.placements.lock().await.expire(max_age);
+			let mut chat = self.chat.lock().await;
+			chat.expire(max_age);
+			if chat.is_empty() {
+				self.chat_drained.notify_waiters();
+			}
+		}
+	}
+
+	async fn flush_board_updates(&self) {
+		let pixels = std::mem::take(&mut *self.pending_pixels.lock().await);
+		if !pixels.is_empty() {
+			self.deliver_board_update(pixels).await
+		}
+	}
+
+	async fn deliver_board_update(&self, pixels: Vec<Pixel>) {
+		let pixels = if self.coalesce_pixels {
+			coalesce(pixels)
+		} else {
+			pixels
+		};
+		self.event_handler().handle_board_update(self, pixels).await
+	}
+
+	async fn websocket_transport(&self, ws_url: &Url) -> Result<Box<dyn Transport>, ConnectError> {
+		#[cfg(unix)]
+		if let Some(path) = self.unix_socket.as_ref() {
+			let stream = tokio::net::UnixStream::connect(path).await
+				.map_err(ConnectError::Io)?;
+			return Ok(Box::new(stream));
+		}
+
+		let host = ws_url.host_str().unwrap_or_default()
+			.trim_start_matches('[')
+			.trim_end_matches(']');
+		let port = ws_url.port_or_known_default().unwrap_or_default();
+		let addresses = resolve::lookup(self.resolver.as_ref(), self.address_family, host, port).await
+			.map_err(ConnectError::Io)?;
+		let tcp = resolve::connect(addresses).await
+			.map_err(ConnectError::Io)?;
+
+		if ws_url.scheme() == "wss" {
+			let ssl = self.websocket_tls.configure()
+				.and_then(|config| config.into_ssl(host))
+				.map_err(|error| ConnectError::Tls(error.into()))?;
+			let mut stream = SslStream::new(ssl, tcp)
+				.map_err(|error| ConnectError::Tls(error.into()))?;
+			Pin::new(&mut stream).connect().await
+				.map_err(ConnectError::Tls)?;
+			Ok(Box::new(stream))
+		} else {
+			Ok(Box::new(tcp))
+		}
+	}
+
+	async fn connect(&self) -> Result<DisconnectReason, ConnectError> {
+		let mut ws_url = self.site_base.join("ws").unwrap();
+
+		match ws_url.scheme() {
+			"http" => ws_url.set_scheme("ws").unwrap(),
+			"https" => ws_url.set_scheme("wss").unwrap(),
+			s => return Err(ConnectError::InvalidSiteScheme(s.to_owned())),
+		};
+
+		let mut ws_request = ws_url.as_str().into_client_request()
+			.map_err(ConnectError::WebsocketConnectFailed)?;
+		let session = self.session().await;
+		if let Some(session) = session.as_ref() {
+			ws_request.headers_mut().insert(header::COOKIE, session.cookie());
+		}
+		if self.websocket_compression {
+			let offer = HeaderValue::from_static(deflate::OFFER);
+			ws_request.headers_mut().insert(header::SEC_WEBSOCKET_EXTENSIONS, offer);
+		}
+
+		let ws_request = self.intercept(ws_request);
+
+		let mut transport = self.websocket_transport(&ws_url).await?;
+		// tungstenite doesn't support compression, so it's undone beneath it
+		if self.websocket_compression {
+			transport = Box::new(Inflate::new(transport));
+		}
+		let (ws_stream, _) = client_async(ws_request, transport)
+			.await
+			.map_err(ConnectError::WebsocketConnectFailed)?;
+
+		// cached is cleared once we reconnect rather than after we disconnect
+		// so that cached data can still be used, even if it is stale.
+		let previous = self.cache.info.get();
+		let previous_fetched_at = self.cache.info.fetched_at();
+		self.clear_cache().await;
+
+		// the connection is only made available once there is info to go with
+		// it, so that giving up on the info doesn't leave a dead connection
+		// looking usable
+		let info = self.connect_info(previous.as_ref(), previous_fetched_at).await
+			.map_err(ConnectError::InfoFailed)?;
+
+		if let Some(capabilities) = self.compat.detect_capabilities(&info) {
+			*self.capabilities.write().unwrap() = capabilities;
+		}
+
+		let (write, mut read) = ws_stream.split();
+		*self.writer.lock().await = Some(write);
+		*self.connected.write().await = true;
+
+		if let Some(previous) = previous {
+			self.info_changed(&previous, &info).await;
+		}
+		self.event_handler().handle_ready(self, &info).await;
+
+		let mut flush_at = None;
+
+		// pxls sends userinfo straight away to signed in users, so a session
+		// which doesn't get one has most likely expired.
+		self.authenticated.store(false, Ordering::SeqCst);
+		*self.user.write().await = None;
+		let mut auth_deadline = session.as_ref()
+			.filter(|_| self.reauthenticator.is_some())
+			.map(|_| Instant::now() + AUTH_TIMEOUT);
+		let mut expired = false;
+		let mut reason = DisconnectReason::Ended;
+		let mut last_received = Instant::now();
+		let mut ping_sent = None;
+
+		loop {
+			self.expire_queued().await;
+			let ping_due = self.heartbeat.read().await
+				.map(|interval| ping_sent.unwrap_or(last_received) + interval);
+			let chat_due = self.chat.lock().await.due();
+			let cooldown = *self.cooldown.borrow();
+			let placement_due = self.placements.lock().await.due(cooldown);
+
+			tokio::select! {
+				next = read.next() => {
+					last_received = Instant::now();
+					ping_sent = None;
+					match next {
+						Some(Ok(WebsocketMessage::Close(frame))) => {
+							if frame.as_ref().is_some_and(|frame| frame.code == CloseCode::Policy) {
+								expired = true;
+							}
+							reason = DisconnectReason::Closed {
+								code: frame.as_ref().map(|frame| frame.code.into()),
+								reason: frame.map(|frame| frame.reason.into_owned()).unwrap_or_default(),
+							};
+						},
+						Some(Ok(message)) => self.handle_message(message).await,
+						Some(Err(error)) => {
+							if let DisconnectReason::Ended = reason {
+								reason = DisconnectReason::Transport(Arc::new(error));
+							}
+						},
+						None => break,
+					}
+				},
+				_ = tokio::time::sleep_until(auth_deadline.unwrap_or_else(Instant::now)), if auth_deadline.is_some() => {
+					auth_deadline = None;
+					if !self.authenticated.load(Ordering::SeqCst) {
+						expired = true;
+						reason = DisconnectReason::SessionExpired;
+						break;
+					}
+				},
+				_ = tokio::time::sleep_until(flush_at.unwrap_or_else(Instant::now)), if flush_at.is_some() => {
+					flush_at = None;
+					self.flush_board_updates().await;
+				},
+				_ = tokio::time::sleep_until(chat_due.unwrap_or_else(Instant::now)), if chat_due.is_some() => {
+					self.send_queued_chat().await;
+				},
+				_ = tokio::time::sleep_until(placement_due.unwrap_or_else(Instant::now)), if placement_due.is_some() => {
+					self.send_queued_placement().await;
+				},
+				_ = tokio::time::sleep_until(ping_due.unwrap_or_else(Instant::now)), if ping_due.is_some() => {
+					if ping_sent.is_some() {
+						reason = DisconnectReason::HeartbeatTimeout;
+						break;
+					}
+					ping_sent = Some(Instant::now());
+					if let Some(writer) = self.writer.lock().await.as_mut() {
+						let _ = writer.send(WebsocketMessage::Ping(vec![])).await;
+					}
+				},
+				_ = self.queued.notified() => (),
+			}
+
+			if let (None, Some(window)) = (flush_at, *self.board_update_window.read().await) {
+				if !self.pending_pixels.lock().await.is_empty() {
+					flush_at = Some(Instant::now() + window);
+				}
+			}
+		}
+
+		self.flush_board_updates().await;
+
+		*self.writer.lock().await = None;
+		*self.connected.write().await = false;
+
+		self.placements.lock().await.answered();
+		if self.offline_policy == OfflinePolicy::Drop {
+			self.clear_chat_queue().await;
+			self.clear_placement_queue().await;
+		}
+		self.event_handler().handle_disconnect(self, reason.clone()).await;
+
+		if expired && self.reauthenticate(session).await {
+			self.reconnect_now.store(true, Ordering::SeqCst);
+		}
+
+		Ok(reason)
+	}
+
+	// Keeps the client connected until the reconnect policy gives up.
+	pub async fn start(&self) {
+		self.run(None).await
+	}
+
+	pub(crate) async fn run(&self, stagger: Option<&Stagger>) {
+		let mut attempt = 0;
+
+		loop {
+			if let Some(stagger) = stagger {
+				stagger.wait().await;
+			}
+
+			let decision = match self.connect().await {
+				Ok(reason) => {
+					attempt = 1;
+					self.reconnect_policy.load().reconnect(ReconnectCause::Disconnected(&reason), attempt)
+				},
+				Err(error) => {
+					attempt += 1;
+					let decision = self.reconnect_policy.load().reconnect(ReconnectCause::Failed(&error), attempt);
+					let error = ClientError::Connect(error);
+					self.event_handler().handle_error(self, error).await;
+					decision
+				},
+			};
+
+			if self.reconnect_now.swap(false, Ordering::SeqCst) {
+				continue;
+			}
+
+			match decision {
+				Reconnect::After(delay) => tokio::time::sleep(delay).await,
+				Reconnect::GiveUp => return,
+			}
+		}
+	}
+
+	// Messages are only broadcast while at least one stream is alive; slow
+	// consumers skip any messages they fall too far behind on.
+	pub fn messages(&self) -> impl Stream<Item = Message> {
+		futures_util::stream::unfold(self.messages.subscribe(), |mut receiver| async move {
+			loop {
+				match receiver.recv().await {
+					Ok(message) => return Some((message, receiver)),
+					Err(RecvError::Lagged(_)) => continue,
+					Err(RecvError::Closed) => return None,
+				}
+			}
+		})
+	}
+
+	pub fn pixels(&self) -> impl Stream<Item = Pixel> {
+		self.messages()
+			.filter_map(|message| async move {
+				match message {
+					Message::Pixel { pixels } => Some(futures_util::stream::iter(pixels)),
+					_ => None,
+				}
+			})
+			.flatten()
+	}
+
+	pub fn pixels_in(&self, region: Rect) -> impl Stream<Item = Pixel> {
+		self.pixels()
+			.filter(move |pixel| std::future::ready(region.contains(pixel.position)))
+	}
+
+	pub fn cooldown_remaining(&self) -> Duration {
+		self.cooldown.borrow()
+			.map(|ready_at| ready_at.saturating_duration_since(Instant::now()))
+			.unwrap_or_default()
+	}
+
+	// Resolves once the most recently reported cooldown has expired.
+	pub async fn wait_for_cooldown(&self) {
+		let mut cooldown = self.cooldown.subscribe();
+		loop {
+			let ready_at = *cooldown.borrow_and_update();
+			match ready_at {
+				Some(ready_at) if ready_at > Instant::now() => {
+					tokio::select! {
+						_ = tokio::time::sleep_until(ready_at) => (),
+						_ = cooldown.changed() => (),
+					}
+				},
+				_ => return,
+			}
+		}
+	}
+
+	// Queues a chat message to be sent as soon as the chat rate limit allows.
+	// Returns the number of messages waiting to be sent.
+	pub async fn send_chat<S: Into<String>>(&self, message: S) -> Result<usize, ChatError> {
+		if !self.capabilities().chat {
+			return Err(ChatError::Disabled);
+		}
+
+		let mut chat = self.chat.lock().await;
+		if self.offline_policy == OfflinePolicy::Drop && !self.is_connected().await {
+			return Ok(chat.len());
+		}
+		let queued = chat.push(message.into());
+		self.queued.notify_one();
+		Ok(queued)
+	}
+
+	// Queues a placement to be sent once connected and off cooldown. Returns
+	// the number of placements waiting to be sent.
+	pub async fn place(&self, pixel: Pixel) -> Result<usize, PlaceError> {
+		if self.refuse_unplaceable {
+			let Pos { x, y } = pixel.position;
+			if !self.is_placeable(x, y).await.map_err(PlaceError::Request)? {
+				return Err(PlaceError::Unplaceable(pixel.position));
+			}
+		}
+
+		let mut placements = self.placements.lock().await;
+		if self.offline_policy == OfflinePolicy::Drop && !self.is_connected().await {
+			return Ok(placements.len());
+		}
+		let queued = placements.push(pixel);
+		self.queued.notify_one();
+		Ok(queued)
+	}
+
+	pub async fn placement_queue_len(&self) -> usize {
+		self.placements.lock().await.len()
+	}
+
+	// Removes and returns all placements which haven't been sent yet.
+	pub async fn clear_placement_queue(&self) -> Vec<Pixel> {
+		self.placements.lock().await.clear()
+	}
+
+	pub async fn chat_queue_len(&self) -> usize {
+		self.chat.lock().await.len()
+	}
+
+	// Removes and returns all chat messages which haven't been sent yet.
+	pub async fn clear_chat_queue(&self) -> Vec<String> {
+		let cleared = self.chat.lock().await.clear();
+		self.chat_drained.notify_waiters();
+		cleared
+	}
+
+	// Resolves once every queued chat message has been sent.
+	pub async fn flush_chat(&self) {
+		loop {
+			let drained = self.chat_drained.notified();
+			if self.chat.lock().await.is_empty() {
+				return;
+			}
+			drained.await;
+		}
+	}
+
+	pub fn capabilities(&self) -> Capabilities {
+		*self.capabilities.read().unwrap()
+	}
+
+	pub async fn is_connected(&self) -> bool {
+		*self.connected.read().await
+	}
+
+	// How quickly pixels are being placed, on the whole board and in each
+	// region given to `ClientBuidler::rate_region`. Only placements seen while
+	// connected count, so the rate starts from zero.
+	pub fn placement_rate(&self) -> PlacementRate {
+		self.placement_rate.rate()
+	}
+
+	// User counts in the order they were received, oldest first.
+	pub fn user_count_history(&self) -> Vec<UserCountSample> {
+		self.user_counts.samples()
+	}
+
+	// The range and average of the user count over the window up to now,
+	// `None` if nothing has been received.
+	pub fn user_count_summary(&self, window: Duration) -> Option<UserCountSummary> {
+		self.user_counts.summary(window)
+	}
+
+	// The signed in user as of the latest messages on this connection, `None`
+	// until userinfo arrives.
+	pub async fn user(&self) -> Option<UserState> {
+		self.user.read().await.clone()
+	}
+
+	// The overrides from the latest userinfo or change by staff on this
+	// connection, `None` until userinfo arrives.
+	pub async fn placement_overrides(&self) -> Option<PlacementOverrides> {
+		self.user.read().await.as_ref()
+			.map(|user| user.placement_overrides.clone())
 	}
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+use futures_util::{Stream, StreamExt};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::Client;
+use crate::messages::Message;
+
+// Spaces out connection attempts shared between several clients.
+#[derive(Debug)]
+pub(crate) struct Stagger {
+	interval: Duration,
+	next: Mutex<Option<Instant>>,
+}
+
+impl Stagger {
+	// Waits for the next free slot and takes it.
+	pub async fn wait(&self) {
+		let slot = {
+			let mut next = self.next.lock().await;
+			let now = Instant::now();
+			let slot = next.map_or(now, |next| next.max(now));
+			*next = Some(slot + self.interval);
+			slot
+		};
+		tokio::time::sleep_until(slot).await;
+	}
+}
+
+// Owns clients for several pxls instances, keyed by a user chosen site id.
+#[derive(Default, Debug)]
+pub struct ClientSet {
+	clients: HashMap<String, Arc<Client>>,
+	stagger: Option<Stagger>,
+}
+
+impl ClientSet {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// Keeps connection attempts from different clients in the set at least
+	// this far apart when run through `start`, so that clients which lost
+	// their connections together, such as after a network outage, don't all
+	// reconnect at once. Each client's own reconnect policy still decides
+	// when it tries again.
+	pub fn stagger(mut self, interval: Duration) -> Self {
+		self.stagger = Some(Stagger { interval, next: Mutex::new(None) });
+		self
+	}
+
+	pub fn insert<S: Into<String>>(&mut self, site: S, client: Client) -> Option<Arc<Client>> {
+		self.clients.insert(site.into(), Arc::new(client))
+	}
+
+	pub fn remove(&mut self, site: &str) -> Option<Arc<Client>> {
+		self.clients.remove(site)
+	}
+
+	pub fn get(&self, site: &str) -> Option<&Arc<Client>> {
+		self.clients.get(site)
+	}
+
+	pub fn sites(&self) -> impl Iterator<Item = &str> {
+		self.clients.keys().map(String::as_str)
+	}
+
+	pub fn clients(&self) -> impl Iterator<Item = (&str, &Arc<Client>)> {
+		self.clients.iter().map(|(site, client)| (site.as_str(), client))
+	}
+
+	// Messages from every client in the set, tagged with the id of the site
+	// they came from. Clients added after this is called are not included.
+	pub fn messages(&self) -> impl Stream<Item = (String, Message)> {
+		let streams = self.clients.iter()
+			.map(|(site, client)| {
+				let site = site.clone();
+				client.messages()
+					.map(move |message| (site.clone(), message))
+					.boxed()
+			});
+
+		futures_util::stream::select_all(streams)
+	}
+
+	// Runs the connection loops of all clients concurrently, staggering their
+	// connection attempts if set to.
+	pub async fn start(&self) {
+		let loops = self.clients.values().map(|client| client.run(self.stagger.as_ref()));
+		futures_util::future::join_all(loops).await;
+	}
+
+	pub async fn connected(&self) -> Vec<&str> {
+		let mut connected = vec![];
+		for (site, client) in &self.clients {
+			if client.is_connected().await {
+				connected.push(site.as_str());
+			}
+		}
+		connected
+	}
+}
@@ -0,0 +1,52 @@
+use futures_util::StreamExt;
+
+use std::sync::Mutex;
+
+use crate::{Client, Pixel};
+
+// Counts live placements by palette index.
+#[derive(Debug, Default)]
+pub struct ColorTracker {
+	counts: Mutex<Vec<u64>>,
+}
+
+impl ColorTracker {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn record(&self, pixels: &[Pixel]) {
+		let mut counts = self.counts.lock().unwrap();
+		for pixel in pixels {
+			let index = usize::from(pixel.color);
+			if counts.len() <= index {
+				counts.resize(index + 1, 0);
+			}
+			counts[index] += 1;
+		}
+	}
+
+	// Placement counts indexed by palette index.
+	pub fn counts(&self) -> Vec<u64> {
+		self.counts.lock().unwrap().clone()
+	}
+
+	pub fn count(&self, color: u8) -> u64 {
+		self.counts.lock().unwrap()
+			.get(usize::from(color))
+			.copied()
+			.unwrap_or(0)
+	}
+
+	pub fn clear(&self) {
+		self.counts.lock().unwrap().clear();
+	}
+
+	pub async fn run(&self, client: &Client) {
+		let pixels = client.pixels();
+		futures_util::pin_mut!(pixels);
+		while let Some(pixel) = pixels.next().await {
+			self.record(std::slice::from_ref(&pixel));
+		}
+	}
+}
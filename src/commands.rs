@@ -0,0 +1,182 @@
+use async_trait::async_trait;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::{Client, EventHandler};
+use crate::messages::ChatMessage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentError {
+	Missing,
+	Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+	Arguments(ArgumentError),
+	Forbidden,
+	Failed(String),
+}
+
+impl From<ArgumentError> for CommandError {
+	fn from(error: ArgumentError) -> Self {
+		CommandError::Arguments(error)
+	}
+}
+
+// Whitespace separated arguments following a command name.
+#[derive(Debug, Clone)]
+pub struct Arguments<'a> {
+	rest: &'a str,
+}
+
+impl<'a> Arguments<'a> {
+	pub fn new(text: &'a str) -> Self {
+		Self { rest: text.trim_start() }
+	}
+
+	pub fn take_str(&mut self) -> Option<&'a str> {
+		if self.rest.is_empty() {
+			return None;
+		}
+
+		let end = self.rest.find(char::is_whitespace).unwrap_or(self.rest.len());
+		let (argument, rest) = self.rest.split_at(end);
+		self.rest = rest.trim_start();
+		Some(argument)
+	}
+
+	pub fn take<T: FromStr>(&mut self) -> Result<T, ArgumentError> {
+		let argument = self.take_str().ok_or(ArgumentError::Missing)?;
+		argument.parse().map_err(|_| ArgumentError::Invalid(argument.to_owned()))
+	}
+
+	pub fn optional<T: FromStr>(&mut self) -> Result<Option<T>, ArgumentError> {
+		match self.take() {
+			Ok(value) => Ok(Some(value)),
+			Err(ArgumentError::Missing) => Ok(None),
+			Err(error) => Err(error),
+		}
+	}
+
+	// Everything which hasn't been consumed yet, such as a free text reason.
+	pub fn rest(&self) -> &'a str {
+		self.rest
+	}
+}
+
+pub struct CommandContext<'a> {
+	pub client: &'a Client,
+	pub message: &'a ChatMessage,
+	pub name: &'a str,
+}
+
+#[async_trait]
+pub trait Command: Send + Sync {
+	async fn run(
+		&self,
+		context: &CommandContext<'_>,
+		arguments: Arguments<'_>,
+	) -> Result<(), CommandError>;
+
+	async fn handle_error(
+		&self,
+		_context: &CommandContext<'_>,
+		_error: CommandError,
+	) {}
+}
+
+struct RegisteredCommand {
+	command: Box<dyn Command>,
+	required_badges: Vec<String>,
+}
+
+// Dispatches prefixed chat messages (e.g. `!roll 20`) to registered commands.
+// Can be used directly as a client's event handler.
+pub struct Commands {
+	prefix: String,
+	commands: HashMap<String, RegisteredCommand>,
+}
+
+impl Commands {
+	pub fn new<S: Into<String>>(prefix: S) -> Self {
+		Self {
+			prefix: prefix.into(),
+			commands: HashMap::new(),
+		}
+	}
+
+	pub fn register<S, C>(self, name: S, command: C) -> Self
+	where S: Into<String>, C: Command + 'static {
+		self.register_restricted(name, &[], command)
+	}
+
+	// Registers a command which may only be used by authors with at least
+	// one of the given badges.
+	pub fn register_restricted<S, C>(mut self, name: S, badges: &[&str], command: C) -> Self
+	where S: Into<String>, C: Command + 'static {
+		let registered = RegisteredCommand {
+			command: Box::new(command),
+			required_badges: badges.iter().map(|&badge| badge.to_owned()).collect(),
+		};
+		self.commands.insert(name.into(), registered);
+		self
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.commands.keys().map(String::as_str)
+	}
+
+	// Runs the command a message invokes, if any. Errors are passed to the
+	// command's error handler as well as being returned.
+	pub async fn dispatch(
+		&self,
+		client: &Client,
+		message: &ChatMessage,
+	) -> Option<Result<(), CommandError>> {
+		let text = message.message_raw.strip_prefix(&self.prefix)?;
+		let mut arguments = Arguments::new(text);
+		let name = arguments.take_str()?;
+		let registered = self.commands.get(name)?;
+
+		let context = CommandContext { client, message, name };
+
+		let permitted = registered.required_badges.is_empty()
+			|| message.badges.iter().any(|badge| {
+				registered.required_badges.contains(&badge.display_name)
+			});
+
+		let result = if permitted {
+			registered.command.run(&context, arguments).await
+		} else {
+			Err(CommandError::Forbidden)
+		};
+
+		if let Err(error) = result.clone() {
+			registered.command.handle_error(&context, error).await;
+		}
+
+		Some(result)
+	}
+}
+
+impl std::fmt::Debug for Commands {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Commands")
+			.field("prefix", &self.prefix)
+			.field("commands", &self.commands.keys().collect::<Vec<_>>())
+			.finish()
+	}
+}
+
+#[async_trait]
+impl EventHandler for Commands {
+	async fn handle_chat_message(
+		&self,
+		client: &Client,
+		message: ChatMessage,
+	) {
+		self.dispatch(client, &message).await;
+	}
+}
@@ -0,0 +1,149 @@
+use serde_json::{Map, Value};
+
+use std::collections::HashMap;
+
+use crate::BoardInfo;
+use crate::messages::{Message, ParseError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+	pub chat: bool,
+	pub binary_pixels: bool,
+}
+
+impl Default for Capabilities {
+	fn default() -> Self {
+		Self {
+			chat: true,
+			binary_pixels: false,
+		}
+	}
+}
+
+// Adjustments for servers whose messages differ from what the parser
+// expects. Messages are first parsed as-is; only those which fail are
+// rewritten according to the profile and parsed again.
+#[derive(Debug, Clone, Default)]
+pub struct CompatProfile {
+	pub name: String,
+	pub capabilities: Capabilities,
+	type_aliases: HashMap<String, String>,
+	// keyed by message type
+	field_aliases: HashMap<String, Vec<(String, String)>>,
+	defaults: HashMap<String, Map<String, Value>>,
+	// capabilities are updated from the server's info once connected
+	detect: bool,
+}
+
+impl CompatProfile {
+	pub fn new<S: Into<String>>(name: S) -> Self {
+		Self { name: name.into(), ..Default::default() }
+	}
+
+	// The current pxls message format, with no adjustments.
+	pub fn standard() -> Self {
+		Self::new("standard")
+	}
+
+	// What clients use unless given a profile. Messages which don't parse are
+	// retried with the legacy adjustments, but the capabilities are those of
+	// current servers apart from what the server's info says otherwise.
+	pub fn auto() -> Self {
+		let mut profile = Self::legacy().capabilities(Capabilities::default());
+		profile.name = "auto".to_owned();
+		profile.detect = true;
+		profile
+	}
+
+	// Capabilities as adjusted by the server's info, see `auto`.
+	pub(crate) fn detect_capabilities(&self, info: &BoardInfo) -> Option<Capabilities> {
+		self.detect.then_some(Capabilities {
+			chat: info.chat_enabled,
+			..self.capabilities
+		})
+	}
+
+	// Older pxls versions which predate factions and several user fields.
+	pub fn legacy() -> Self {
+		Self::new("legacy")
+			.default_field("userinfo", "roles", Value::Array(vec![]))
+			.default_field("userinfo", "method", Value::from("unknown"))
+			.default_field("userinfo", "placementOverrides", Value::Object(Map::new()))
+			.default_field("userinfo", "chatBanned", Value::Bool(false))
+			.default_field("userinfo", "renameRequested", Value::Bool(false))
+			.default_field("userinfo", "discordName", Value::Null)
+			.default_field("userinfo", "chatNameColor", Value::from(0))
+			.default_field("userinfo", "pixelCountAllTime", Value::from(0))
+			.default_field("pixelCounts", "pixelCountAllTime", Value::from(0))
+			.alias_type("userCount", "users")
+	}
+
+	pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+		self.capabilities = capabilities;
+		self
+	}
+
+	pub fn alias_type<A, B>(mut self, from: A, to: B) -> Self
+	where A: Into<String>, B: Into<String> {
+		self.type_aliases.insert(from.into(), to.into());
+		self
+	}
+
+	// Renames a field of a message type. The type is the one after any type
+	// aliases have been applied.
+	pub fn alias_field<T, A, B>(mut self, message_type: T, from: A, to: B) -> Self
+	where T: Into<String>, A: Into<String>, B: Into<String> {
+		self.field_aliases.entry(message_type.into())
+			.or_default()
+			.push((from.into(), to.into()));
+		self
+	}
+
+	// Provides a value for a field which is missing from a message type.
+	pub fn default_field<T, F>(mut self, message_type: T, field: F, value: Value) -> Self
+	where T: Into<String>, F: Into<String> {
+		self.defaults.entry(message_type.into())
+			.or_default()
+			.insert(field.into(), value);
+		self
+	}
+
+	pub fn is_standard(&self) -> bool {
+		self.type_aliases.is_empty()
+			&& self.field_aliases.is_empty()
+			&& self.defaults.is_empty()
+	}
+
+	pub fn normalize(&self, value: &mut Value) {
+		let object = match value.as_object_mut() {
+			Some(object) => object,
+			None => return,
+		};
+
+		let mut message_type = match object.get("type").and_then(Value::as_str) {
+			Some(message_type) => message_type.to_owned(),
+			None => return,
+		};
+
+		if let Some(alias) = self.type_aliases.get(&message_type) {
+			message_type = alias.clone();
+			object.insert("type".to_owned(), Value::from(alias.as_str()));
+		}
+
+		for (from, to) in self.field_aliases.get(&message_type).into_iter().flatten() {
+			if let Some(field) = object.remove(from) {
+				object.entry(to.as_str()).or_insert(field);
+			}
+		}
+
+		for (field, default) in self.defaults.get(&message_type).into_iter().flatten() {
+			object.entry(field.as_str()).or_insert_with(|| default.clone());
+		}
+	}
+
+	pub fn parse(&self, text: &str) -> Result<Message, ParseError> {
+		let mut value = serde_json::from_str(text).map_err(ParseError::Json)?;
+		self.normalize(&mut value);
+		serde_json::from_value(value).map_err(ParseError::Json)
+	}
+}
@@ -0,0 +1,112 @@
+use hyper::Uri;
+use hyper::client::HttpConnector;
+use hyper::client::connect::{Connected, Connection};
+use hyper_openssl::{HttpsConnector, MaybeHttpsStream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+use std::future::Future;
+use std::io;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::resolve::HyperResolver;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// Makes the connections for REST requests, either over the network as usual
+// or to a local server's unix socket.
+#[derive(Clone)]
+pub(crate) enum Connector {
+	Tcp(HttpsConnector<HttpConnector<HyperResolver>>),
+	// requests are sent in plain http whatever the url's scheme is
+	#[cfg(unix)]
+	Unix(PathBuf),
+}
+
+pub(crate) enum Stream {
+	Tcp(MaybeHttpsStream<TcpStream>),
+	#[cfg(unix)]
+	Unix(UnixStream),
+}
+
+impl hyper::service::Service<Uri> for Connector {
+	type Response = Stream;
+	type Error = BoxError;
+	type Future = Pin<Box<dyn Future<Output = Result<Stream, BoxError>> + Send>>;
+
+	fn poll_ready(&mut self, context: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+		match self {
+			Connector::Tcp(connector) => connector.poll_ready(context),
+			#[cfg(unix)]
+			Connector::Unix(_) => Poll::Ready(Ok(())),
+		}
+	}
+
+	fn call(&mut self, uri: Uri) -> Self::Future {
+		match self {
+			Connector::Tcp(connector) => {
+				let connecting = connector.call(uri);
+				Box::pin(async move { connecting.await.map(Stream::Tcp) })
+			},
+			#[cfg(unix)]
+			Connector::Unix(path) => {
+				let path = path.clone();
+				Box::pin(async move {
+					let stream = UnixStream::connect(path).await?;
+					Ok(Stream::Unix(stream))
+				})
+			},
+		}
+	}
+}
+
+impl Connection for Stream {
+	fn connected(&self) -> Connected {
+		match self {
+			Stream::Tcp(stream) => stream.connected(),
+			#[cfg(unix)]
+			Stream::Unix(_) => Connected::new(),
+		}
+	}
+}
+
+impl AsyncRead for Stream {
+	fn poll_read(self: Pin<&mut Self>, context: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			Stream::Tcp(stream) => Pin::new(stream).poll_read(context, buf),
+			#[cfg(unix)]
+			Stream::Unix(stream) => Pin::new(stream).poll_read(context, buf),
+		}
+	}
+}
+
+impl AsyncWrite for Stream {
+	fn poll_write(self: Pin<&mut Self>, context: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		match self.get_mut() {
+			Stream::Tcp(stream) => Pin::new(stream).poll_write(context, buf),
+			#[cfg(unix)]
+			Stream::Unix(stream) => Pin::new(stream).poll_write(context, buf),
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			Stream::Tcp(stream) => Pin::new(stream).poll_flush(context),
+			#[cfg(unix)]
+			Stream::Unix(stream) => Pin::new(stream).poll_flush(context),
+		}
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			Stream::Tcp(stream) => Pin::new(stream).poll_shutdown(context),
+			#[cfg(unix)]
+			Stream::Unix(stream) => Pin::new(stream).poll_shutdown(context),
+		}
+	}
+}
@@ -0,0 +1,290 @@
+use bytes::{Buf, BytesMut};
+use flate2::{Decompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+// what the client offers in its handshake
+pub(crate) const OFFER: &str = "permessage-deflate; client_no_context_takeover";
+
+// Compressed messages larger than this, before or after inflating, are
+// treated as an error rather than buffered. It matches tungstenite's own
+// default message size limit.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+// Appended to every compressed message before inflating, see RFC 7692.
+pub(crate) const MESSAGE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+pub(crate) const FIN: u8 = 0x80;
+pub(crate) const RSV1: u8 = 0x40;
+pub(crate) const OPCODE: u8 = 0x0f;
+const CONTINUATION: u8 = 0x0;
+const MASKED: u8 = 0x80;
+
+enum Mode {
+	// waiting for the end of the server's handshake response
+	Handshake,
+	// the server didn't accept compression
+	Passthrough,
+	Frames,
+}
+
+// A compressed message whose fragments are still arriving.
+struct Partial {
+	opcode: u8,
+	payload: Vec<u8>,
+}
+
+// Undoes permessage-deflate on frames from the server before tungstenite,
+// which doesn't support the extension, sees them. Compressed messages are
+// passed on as a single uncompressed frame and everything else is passed on
+// untouched. Outgoing messages are never compressed, which the extension
+// allows, so writes go straight through.
+pub(crate) struct Inflate<T> {
+	inner: T,
+	mode: Mode,
+	decompress: Decompress,
+	// whether the server starts every message with a fresh compression window
+	reset_each_message: bool,
+	partial: Option<Partial>,
+	// read from the server and not yet handled
+	raw: BytesMut,
+	// ready to be read by tungstenite
+	out: BytesMut,
+}
+
+impl<T> Inflate<T> {
+	pub fn new(inner: T) -> Self {
+		Self {
+			inner,
+			mode: Mode::Handshake,
+			decompress: Decompress::new(false),
+			reset_each_message: false,
+			partial: None,
+			raw: BytesMut::new(),
+			out: BytesMut::new(),
+		}
+	}
+
+	// Handles as much of the raw input as possible. Returns whether anything
+	// was, or more input is needed.
+	fn process(&mut self) -> io::Result<bool> {
+		match self.mode {
+			Mode::Handshake => {
+				let end = match self.raw.windows(4).position(|window| window == b"\r\n\r\n") {
+					Some(end) => end + 4,
+					None => return Ok(false),
+				};
+
+				let response = self.raw.split_to(end);
+				self.mode = match negotiated(&response) {
+					Some(reset_each_message) => {
+						self.reset_each_message = reset_each_message;
+						Mode::Frames
+					},
+					None => Mode::Passthrough,
+				};
+				self.out.extend_from_slice(&response);
+				Ok(true)
+			},
+			Mode::Passthrough => {
+				let progress = !self.raw.is_empty();
+				let raw = self.raw.split();
+				self.out.extend_from_slice(&raw);
+				Ok(progress)
+			},
+			Mode::Frames => self.process_frame(),
+		}
+	}
+
+	fn process_frame(&mut self) -> io::Result<bool> {
+		let header = match parse_header(&self.raw)? {
+			Some(header) => header,
+			None => return Ok(false),
+		};
+		if self.raw.len() < header.length + header.payload {
+			return Ok(false);
+		}
+
+		let first = self.raw[0];
+		let opcode = first & OPCODE;
+		let control = opcode & 0x8 != 0;
+		let compressed = first & RSV1 != 0;
+
+		if control || (opcode == CONTINUATION && self.partial.is_none()) || (opcode != CONTINUATION && !compressed) {
+			let frame = self.raw.split_to(header.length + header.payload);
+			self.out.extend_from_slice(&frame);
+			return Ok(true);
+		}
+
+		self.raw.advance(header.length);
+		let mut payload = self.raw.split_to(header.payload);
+		if let Some(mask) = header.mask {
+			for (index, byte) in payload.iter_mut().enumerate() {
+				*byte ^= mask[index % 4];
+			}
+		}
+
+		let partial = self.partial.get_or_insert_with(|| Partial { opcode, payload: vec![] });
+		if partial.payload.len() + payload.len() > MAX_MESSAGE_SIZE {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "compressed message is too large"));
+		}
+		partial.payload.extend_from_slice(&payload);
+
+		if first & FIN != 0 {
+			let Partial { opcode, mut payload } = self.partial.take().unwrap();
+			payload.extend_from_slice(&MESSAGE_TAIL);
+			let message = self.inflate(&payload)?;
+			write_frame(&mut self.out, FIN | opcode, &message);
+		}
+
+		Ok(true)
+	}
+
+	fn inflate(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+		let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+		let mut output = Vec::with_capacity(input.len() * 4);
+		let start_in = self.decompress.total_in();
+		loop {
+			let consumed = (self.decompress.total_in() - start_in) as usize;
+			if output.len() == output.capacity() {
+				output.reserve(output.capacity().max(1024));
+			}
+
+			let status = self.decompress.decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+				.map_err(|_| invalid("compressed message is corrupt"))?;
+			if output.len() > MAX_MESSAGE_SIZE {
+				return Err(invalid("compressed message is too large"));
+			}
+
+			let consumed = (self.decompress.total_in() - start_in) as usize;
+			let finished = consumed == input.len() && output.len() < output.capacity();
+			if finished || status == Status::StreamEnd {
+				break;
+			}
+		}
+
+		if self.reset_each_message {
+			self.decompress.reset(false);
+		}
+		Ok(output)
+	}
+}
+
+pub(crate) struct Header {
+	// of the header itself
+	pub length: usize,
+	pub payload: usize,
+	pub mask: Option<[u8; 4]>,
+}
+
+pub(crate) fn parse_header(raw: &[u8]) -> io::Result<Option<Header>> {
+	if raw.len() < 2 {
+		return Ok(None);
+	}
+
+	let (mut length, payload) = match raw[1] & 0x7f {
+		126 if raw.len() >= 4 => (4, u64::from(u16::from_be_bytes([raw[2], raw[3]]))),
+		127 if raw.len() >= 10 => (10, u64::from_be_bytes(raw[2..10].try_into().unwrap())),
+		126 | 127 => return Ok(None),
+		payload => (2, u64::from(payload)),
+	};
+	let payload = usize::try_from(payload).ok()
+		.filter(|&payload| payload <= MAX_MESSAGE_SIZE)
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "frame is too large"))?;
+
+	let mask = if raw[1] & MASKED != 0 {
+		if raw.len() < length + 4 {
+			return Ok(None);
+		}
+		let mask = raw[length..length + 4].try_into().unwrap();
+		length += 4;
+		Some(mask)
+	} else {
+		None
+	};
+
+	Ok(Some(Header { length, payload, mask }))
+}
+
+// Writes an unmasked frame, `first` being its first byte with the flags and
+// opcode.
+pub(crate) fn write_frame(out: &mut BytesMut, first: u8, payload: &[u8]) {
+	out.extend_from_slice(&[first]);
+	match payload.len() {
+		length @ 0..=125 => out.extend_from_slice(&[length as u8]),
+		length @ 126..=0xffff => {
+			out.extend_from_slice(&[126]);
+			out.extend_from_slice(&(length as u16).to_be_bytes());
+		},
+		length => {
+			out.extend_from_slice(&[127]);
+			out.extend_from_slice(&(length as u64).to_be_bytes());
+		},
+	}
+	out.extend_from_slice(payload);
+}
+
+// Whether the server's handshake response accepted compression, and if so
+// whether it resets its window for every message.
+fn negotiated(response: &[u8]) -> Option<bool> {
+	let response = std::str::from_utf8(response).ok()?;
+	response.split("\r\n")
+		.filter_map(|line| line.split_once(':'))
+		.filter(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-extensions"))
+		.flat_map(|(_, value)| value.split(','))
+		.find_map(|extension| {
+			let mut parameters = extension.split(';').map(str::trim);
+			(parameters.next()? == "permessage-deflate")
+				.then(|| parameters.any(|parameter| parameter == "server_no_context_takeover"))
+		})
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Inflate<T> {
+	fn poll_read(self: Pin<&mut Self>, context: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		let this = self.get_mut();
+
+		loop {
+			if !this.out.is_empty() {
+				let length = buf.remaining().min(this.out.len());
+				buf.put_slice(&this.out.split_to(length));
+				return Poll::Ready(Ok(()));
+			}
+
+			if this.process()? {
+				continue;
+			}
+
+			let mut chunk = [0; 8192];
+			let mut read = ReadBuf::new(&mut chunk);
+			ready!(Pin::new(&mut this.inner).poll_read(context, &mut read))?;
+			if read.filled().is_empty() {
+				// the connection ended, anything left over is passed on as it is
+				let raw = this.raw.split();
+				this.out.extend_from_slice(&raw);
+				if this.out.is_empty() {
+					return Poll::Ready(Ok(()));
+				}
+				continue;
+			}
+			this.raw.extend_from_slice(read.filled());
+		}
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Inflate<T> {
+	fn poll_write(self: Pin<&mut Self>, context: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		Pin::new(&mut self.get_mut().inner).poll_write(context, buf)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_flush(context)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_shutdown(context)
+	}
+}
@@ -0,0 +1,66 @@
+use image::{Rgba, RgbaImage};
+
+use std::collections::HashMap;
+
+// Recovers the board pixels from a styled template image, where each pixel
+// is drawn as a `scale` by `scale` block holding a symbol in that pixel's
+// color on a transparent background. Returns `None` if the image isn't made
+// of whole blocks.
+pub fn detemplatize(image: &RgbaImage, scale: u32) -> Option<RgbaImage> {
+	if scale == 0 || !image.width().is_multiple_of(scale) || !image.height().is_multiple_of(scale) {
+		return None;
+	}
+
+	if scale == 1 {
+		return Some(image.clone());
+	}
+
+	let width = image.width() / scale;
+	let height = image.height() / scale;
+	Some(RgbaImage::from_fn(width, height, |x, y| {
+		block_color(image, x * scale, y * scale, scale).unwrap_or(Rgba([0; 4]))
+	}))
+}
+
+// The largest block size for which every block of the image has no more than
+// one opaque color, or 1 if the image doesn't look styled. An image which is
+// a single color throughout can't be told apart from a styled one.
+pub fn detect_scale(image: &RgbaImage) -> u32 {
+	let largest = image.width().min(image.height());
+	(2..=largest).rev()
+		.filter(|&scale| image.width().is_multiple_of(scale) && image.height().is_multiple_of(scale))
+		.find(|&scale| {
+			(0..image.height() / scale).all(|y| {
+				(0..image.width() / scale).all(|x| {
+					block_colors(image, x * scale, y * scale, scale).len() <= 1
+				})
+			})
+		})
+		.unwrap_or(1)
+}
+
+fn is_opaque(pixel: &Rgba<u8>) -> bool {
+	pixel.0[3] >= u8::MAX / 2
+}
+
+fn block_colors(image: &RgbaImage, left: u32, top: u32, scale: u32) -> HashMap<[u8; 3], usize> {
+	let mut colors = HashMap::new();
+	for y in top..top + scale {
+		for x in left..left + scale {
+			let pixel = image.get_pixel(x, y);
+			if is_opaque(pixel) {
+				let [r, g, b, _] = pixel.0;
+				*colors.entry([r, g, b]).or_default() += 1;
+			}
+		}
+	}
+	colors
+}
+
+// The most common opaque color in a block, since symbols may be antialiased
+// into their surroundings.
+fn block_color(image: &RgbaImage, left: u32, top: u32, scale: u32) -> Option<Rgba<u8>> {
+	block_colors(image, left, top, scale).into_iter()
+		.max_by_key(|&(color, count)| (count, color))
+		.map(|([r, g, b], _)| Rgba([r, g, b, u8::MAX]))
+}
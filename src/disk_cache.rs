@@ -0,0 +1,85 @@
+use bytes::Bytes;
+use hyper::header::HeaderValue;
+
+use std::path::{Path, PathBuf};
+
+use crate::CanvasCode;
+
+// Canvas codes come from the server so they shouldn't be trusted to be safe
+// path components.
+pub(crate) fn path_component(canvas_code: &CanvasCode) -> String {
+	canvas_code.as_str().chars()
+		.filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+		.collect()
+}
+
+// A cached response along with the validators needed to revalidate it.
+#[derive(Debug)]
+pub(crate) struct DiskEntry {
+	pub body: Bytes,
+	pub etag: Option<HeaderValue>,
+	pub last_modified: Option<HeaderValue>,
+}
+
+// Persists responses so they can be revalidated rather than downloaded again
+// after a restart. Board info is stored at the top level and everything else
+// in a directory per canvas.
+#[derive(Debug)]
+pub(crate) struct DiskCache {
+	dir: PathBuf,
+}
+
+impl DiskCache {
+	pub fn new(dir: PathBuf) -> Self {
+		Self { dir }
+	}
+
+	pub fn path(&self, canvas_code: Option<&CanvasCode>, endpoint: &str) -> PathBuf {
+		match canvas_code {
+			Some(code) => self.dir.join(path_component(code)).join(endpoint),
+			None => self.dir.join(endpoint),
+		}
+	}
+
+	fn validators_path(path: &Path) -> PathBuf {
+		path.with_extension("validators")
+	}
+
+	pub async fn read(&self, path: &Path) -> Option<DiskEntry> {
+		let body = tokio::fs::read(path).await.ok()?;
+		let validators = tokio::fs::read_to_string(Self::validators_path(path)).await.ok()?;
+		let mut lines = validators.lines()
+			.map(|line| HeaderValue::from_str(line).ok().filter(|value| !value.is_empty()));
+
+		Some(DiskEntry {
+			body: Bytes::from(body),
+			etag: lines.next().flatten(),
+			last_modified: lines.next().flatten(),
+		})
+	}
+
+	// Failing to write only means the next start is slower, so errors are
+	// ignored.
+	pub async fn write(&self, path: &Path, entry: &DiskEntry) {
+		let as_str = |value: &Option<HeaderValue>| {
+			value.as_ref()
+				.and_then(|value| value.to_str().ok())
+				.unwrap_or_default()
+				.to_owned()
+		};
+		let validators = format!("{}\n{}\n", as_str(&entry.etag), as_str(&entry.last_modified));
+
+		if let Some(parent) = path.parent() {
+			if tokio::fs::create_dir_all(parent).await.is_err() {
+				return;
+			}
+		}
+
+		// the validators are written last so that a partially written body is
+		// never mistaken for a complete one
+		let _ = tokio::fs::remove_file(Self::validators_path(path)).await;
+		if tokio::fs::write(path, &entry.body).await.is_ok() {
+			let _ = tokio::fs::write(Self::validators_path(path), validators).await;
+		}
+	}
+}
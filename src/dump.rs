@@ -0,0 +1,211 @@
+use bytes::Bytes;
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{CanvasCode, Color, RequestError, Snapshot};
+
+const MAGIC: &[u8; 8] = b"PXLSDUMP";
+const VERSION: u8 = 1;
+
+// A board saved to a file, with everything needed to make sense of it away
+// from the server. All numbers are big-endian:
+//
+// - the magic bytes "PXLSDUMP" and a version byte
+// - when the dump was taken, as u64 milliseconds since the unix epoch
+// - the canvas code as a u16 length followed by that many bytes of utf-8
+// - the width and height as u32s
+// - a u16 count of palette entries, each a u16 length and name followed by
+//   three bytes of rgb
+// - a u8 count of buffers, each a kind byte followed by one byte per pixel
+#[derive(Debug, Clone)]
+pub struct Dump {
+	pub canvas_code: CanvasCode,
+	pub width: usize,
+	pub height: usize,
+	pub palette: Vec<Color>,
+	pub taken_at: SystemTime,
+	pub colors: Bytes,
+	pub initial: Option<Bytes>,
+	pub mask: Option<Bytes>,
+	pub virginmap: Option<Bytes>,
+	pub heatmap: Option<Bytes>,
+}
+
+#[derive(Debug)]
+pub enum DumpError {
+	Request(RequestError),
+	Io(io::Error),
+	// the data doesn't start with the magic bytes
+	NotADump,
+	UnsupportedVersion(u8),
+	InvalidText(std::string::FromUtf8Error),
+	UnknownBuffer(u8),
+	MissingColors,
+	// a buffer didn't have one value for each pixel of the board
+	BufferSize { expected: usize, actual: usize },
+	// a value is too large to be stored in the format, or a dump's board is
+	// too large to be loaded
+	TooLarge,
+}
+
+impl From<io::Error> for DumpError {
+	fn from(error: io::Error) -> Self {
+		DumpError::Io(error)
+	}
+}
+
+const COLORS: u8 = 0;
+const INITIAL: u8 = 1;
+const MASK: u8 = 2;
+const VIRGINMAP: u8 = 3;
+const HEATMAP: u8 = 4;
+
+fn write_text<W: Write>(writer: &mut W, text: &str) -> Result<(), DumpError> {
+	let length = u16::try_from(text.len()).map_err(|_| DumpError::TooLarge)?;
+	writer.write_all(&length.to_be_bytes())?;
+	writer.write_all(text.as_bytes())?;
+	Ok(())
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+	let mut bytes = [0; N];
+	reader.read_exact(&mut bytes)?;
+	Ok(bytes)
+}
+
+fn read_text<R: Read>(reader: &mut R) -> Result<String, DumpError> {
+	let length = u16::from_be_bytes(read_array(reader)?);
+	let mut bytes = vec![0; usize::from(length)];
+	reader.read_exact(&mut bytes)?;
+	String::from_utf8(bytes).map_err(DumpError::InvalidText)
+}
+
+impl Dump {
+	fn buffers(&self) -> impl Iterator<Item = (u8, &Bytes)> {
+		[
+			(COLORS, Some(&self.colors)),
+			(INITIAL, self.initial.as_ref()),
+			(MASK, self.mask.as_ref()),
+			(VIRGINMAP, self.virginmap.as_ref()),
+			(HEATMAP, self.heatmap.as_ref()),
+		]
+		.into_iter()
+		.filter_map(|(kind, buffer)| Some((kind, buffer?)))
+	}
+
+	pub fn export<W: Write>(&self, mut writer: W) -> Result<(), DumpError> {
+		let expected = self.width * self.height;
+		if let Some((_, buffer)) = self.buffers().find(|(_, buffer)| buffer.len() != expected) {
+			return Err(DumpError::BufferSize { expected, actual: buffer.len() });
+		}
+
+		writer.write_all(MAGIC)?;
+		writer.write_all(&[VERSION])?;
+
+		let taken_at = self.taken_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+		let taken_at = u64::try_from(taken_at.as_millis()).map_err(|_| DumpError::TooLarge)?;
+		writer.write_all(&taken_at.to_be_bytes())?;
+
+		write_text(&mut writer, self.canvas_code.as_str())?;
+		for dimension in [self.width, self.height] {
+			let dimension = u32::try_from(dimension).map_err(|_| DumpError::TooLarge)?;
+			writer.write_all(&dimension.to_be_bytes())?;
+		}
+
+		let colors = u16::try_from(self.palette.len()).map_err(|_| DumpError::TooLarge)?;
+		writer.write_all(&colors.to_be_bytes())?;
+		for color in &self.palette {
+			write_text(&mut writer, &color.name)?;
+			writer.write_all(&color.value)?;
+		}
+
+		writer.write_all(&[self.buffers().count() as u8])?;
+		for (kind, buffer) in self.buffers() {
+			writer.write_all(&[kind])?;
+			writer.write_all(buffer)?;
+		}
+
+		writer.flush()?;
+		Ok(())
+	}
+
+	pub fn import<R: Read>(mut reader: R) -> Result<Self, DumpError> {
+		let magic: [u8; 8] = read_array(&mut reader)?;
+		if &magic != MAGIC {
+			return Err(DumpError::NotADump);
+		}
+
+		let [version] = read_array(&mut reader)?;
+		if version != VERSION {
+			return Err(DumpError::UnsupportedVersion(version));
+		}
+
+		let taken_at = u64::from_be_bytes(read_array(&mut reader)?);
+		let taken_at = UNIX_EPOCH + Duration::from_millis(taken_at);
+		let canvas_code = CanvasCode::new(read_text(&mut reader)?);
+		let width = u32::from_be_bytes(read_array(&mut reader)?) as usize;
+		let height = u32::from_be_bytes(read_array(&mut reader)?) as usize;
+
+		let colors = u16::from_be_bytes(read_array(&mut reader)?);
+		let palette = (0..colors)
+			.map(|_| {
+				let name = read_text(&mut reader)?;
+				let value = read_array(&mut reader)?;
+				Ok(Color { name, value })
+			})
+			.collect::<Result<Vec<_>, DumpError>>()?;
+
+		let mut dump = Self {
+			canvas_code,
+			width,
+			height,
+			palette,
+			taken_at,
+			colors: Bytes::new(),
+			initial: None,
+			mask: None,
+			virginmap: None,
+			heatmap: None,
+		};
+
+		// the size comes from the file, so nothing is allocated for a buffer
+		// until its data has actually been read
+		let expected = width.checked_mul(height).ok_or(DumpError::TooLarge)?;
+
+		let [buffers] = read_array(&mut reader)?;
+		let mut colors = None;
+		for _ in 0..buffers {
+			let [kind] = read_array(&mut reader)?;
+			let mut buffer = Vec::new();
+			(&mut reader).take(expected as u64).read_to_end(&mut buffer)?;
+			if buffer.len() != expected {
+				return Err(DumpError::BufferSize { expected, actual: buffer.len() });
+			}
+			let buffer = Some(Bytes::from(buffer));
+
+			match kind {
+				COLORS => colors = buffer,
+				INITIAL => dump.initial = buffer,
+				MASK => dump.mask = buffer,
+				VIRGINMAP => dump.virginmap = buffer,
+				HEATMAP => dump.heatmap = buffer,
+				kind => return Err(DumpError::UnknownBuffer(kind)),
+			}
+		}
+
+		dump.colors = colors.ok_or(DumpError::MissingColors)?;
+		Ok(dump)
+	}
+
+	pub fn to_snapshot(&self) -> Snapshot {
+		Snapshot {
+			canvas_code: self.canvas_code.clone(),
+			width: self.width,
+			height: self.height,
+			palette: self.palette.clone(),
+			colors: self.colors.clone(),
+			taken_at: self.taken_at,
+		}
+	}
+}
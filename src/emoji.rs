@@ -0,0 +1,79 @@
+use serde::{Deserialize, Deserializer};
+
+use std::collections::HashMap;
+use std::ops::Deref;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Emoji {
+	pub emoji: String,
+	pub name: String,
+}
+
+// The custom emoji list from /info, indexed by name and by emoji file.
+#[derive(Debug, Clone, Default)]
+pub struct EmojiSet {
+	emoji: Vec<Emoji>,
+	by_name: HashMap<String, usize>,
+	by_emoji: HashMap<String, usize>,
+}
+
+impl EmojiSet {
+	pub fn by_name(&self, name: &str) -> Option<&Emoji> {
+		self.by_name.get(name).map(|&index| &self.emoji[index])
+	}
+
+	pub fn by_emoji(&self, emoji: &str) -> Option<&Emoji> {
+		self.by_emoji.get(emoji).map(|&index| &self.emoji[index])
+	}
+
+	// Finds every `:name:` reference to a known emoji in a chat message.
+	pub fn find_in<'a>(&'a self, text: &'a str) -> Vec<&'a Emoji> {
+		let mut found = vec![];
+		let mut segments = text.split(':').skip(1).peekable();
+
+		while let Some(segment) = segments.next() {
+			// the final segment has no closing colon
+			if segments.peek().is_none() {
+				break;
+			}
+
+			if let Some(emoji) = self.by_name(segment) {
+				found.push(emoji);
+				// the closing colon can't also open the next reference
+				segments.next();
+			}
+		}
+
+		found
+	}
+}
+
+impl From<Vec<Emoji>> for EmojiSet {
+	fn from(emoji: Vec<Emoji>) -> Self {
+		let by_name = emoji.iter()
+			.enumerate()
+			.map(|(index, emoji)| (emoji.name.clone(), index))
+			.collect();
+		let by_emoji = emoji.iter()
+			.enumerate()
+			.map(|(index, emoji)| (emoji.emoji.clone(), index))
+			.collect();
+
+		Self { emoji, by_name, by_emoji }
+	}
+}
+
+impl Deref for EmojiSet {
+	type Target = [Emoji];
+
+	fn deref(&self) -> &Self::Target {
+		&self.emoji
+	}
+}
+
+impl<'de> Deserialize<'de> for EmojiSet {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where D: Deserializer<'de> {
+		Vec::<Emoji>::deserialize(deserializer).map(Self::from)
+	}
+}
@@ -1,22 +1,25 @@
 
 use async_trait::async_trait;
+use url::Url;
+
+use std::time::Duration;
 
 use crate::{messages::{
 	AcknowledgeType,
 	PlacementOverrides, ChatMessage, ChatBan, UserUpdate, UserFaction, Notification, Pixel, Role, User,
-}, Client};
+}, BoardInfo, CacheKind, Client, ClientError, ClientWarning, DisconnectReason, Grief, Pos, StatsMilestoneEntry, Template, TemplateProgress};
 
 #[async_trait]
 pub trait EventHandler: Send + Sync {
-	async fn handle_ready(&self, client: &Client) {}
-	async fn handle_disconnect(&self, client: &Client) {}
+	// Called once board info has been fetched for a new connection.
+	async fn handle_ready(&self, client: &Client, info: &BoardInfo) {}
+	async fn handle_disconnect(&self, client: &Client, reason: DisconnectReason) {}
 
 	async fn handle_acknowledge(
 		&self,
 		client: &Client,
 		acknowledge_for: AcknowledgeType,
-		x: usize,
-		y: usize,
+		position: Pos,
 	) {}
 
 	async fn handle_overrides(
@@ -115,7 +118,7 @@ pub trait EventHandler: Send + Sync {
 	async fn handle_cooldown(
 		&self,
 		client: &Client,
-		wait: f32,
+		wait: Duration,
 	) {}
 
 	async fn handle_faction_clear(
@@ -209,9 +212,68 @@ pub trait EventHandler: Send + Sync {
 		count: usize,
 	) {}
 
+	// sizes are (width, height)
+	async fn handle_board_resize(
+		&self,
+		client: &Client,
+		old: (usize, usize),
+		new: (usize, usize),
+	) {}
+
+	// A cached value was fetched again in the background, see
+	// `Client::revalidate` and `Client::refresh_buffers`.
+	async fn handle_cache_refreshed(&self, client: &Client, kind: CacheKind) {}
+
+	async fn handle_milestone(
+		&self,
+		client: &Client,
+		milestone: StatsMilestoneEntry,
+	) {}
+
+	async fn handle_template_progress(
+		&self,
+		client: &Client,
+		template: &Template,
+		progress: TemplateProgress,
+	) {}
+
+	async fn handle_grief(
+		&self,
+		client: &Client,
+		template: &Template,
+		grief: Grief,
+	) {}
+
+	async fn handle_rate_limited(
+		&self,
+		client: &Client,
+		location: Url,
+		retry_after: Duration,
+	) {}
+
+	// Called with every text frame before it's parsed, whether or not it
+	// turns out to be a known message.
+	async fn handle_raw(
+		&self,
+		client: &Client,
+		text: &str,
+	) {}
+
 	async fn handle_unknown(
 		&self,
 		client: &Client,
 		packet: String,
 	) {}
+
+	async fn handle_error(
+		&self,
+		client: &Client,
+		error: ClientError,
+	) {}
+
+	async fn handle_warning(
+		&self,
+		client: &Client,
+		warning: ClientWarning,
+	) {}
 }
\ No newline at end of file
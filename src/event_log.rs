@@ -0,0 +1,56 @@
+use tokio::sync::Mutex;
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::log_writer::{LogWriter, timestamped_line};
+use crate::messages::Message;
+
+// Keeps a record of every message received, in the same format as `ChatLog`.
+// Recording can be paused and resumed while the client is running.
+#[derive(Debug)]
+pub struct EventLog {
+	writer: Mutex<LogWriter>,
+	enabled: AtomicBool,
+}
+
+impl EventLog {
+	pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+		Self {
+			writer: Mutex::new(LogWriter::new(path.into())),
+			enabled: AtomicBool::new(true),
+		}
+	}
+
+	// Starts a new file once the current one would grow past `max_size`
+	// bytes, keeping up to `keep` old files alongside it.
+	pub fn rotate(mut self, max_size: u64, keep: usize) -> Self {
+		self.writer.get_mut().rotate(max_size, keep);
+		self
+	}
+
+	// Gzips the log. The file can be read with any gzip tool, but the size
+	// limit for rotation applies to the compressed size.
+	pub fn compress(mut self, compress: bool) -> Self {
+		self.writer.get_mut().compress(compress);
+		self
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.enabled.load(Ordering::SeqCst)
+	}
+
+	pub fn set_enabled(&self, enabled: bool) {
+		self.enabled.store(enabled, Ordering::SeqCst);
+	}
+
+	pub(crate) async fn record(&self, message: &Message) -> io::Result<()> {
+		if !self.is_enabled() {
+			return Ok(());
+		}
+
+		let line = timestamped_line(message)?;
+		self.writer.lock().await.append(&line).await
+	}
+}
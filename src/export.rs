@@ -0,0 +1,141 @@
+use serde::{Serialize, Serializer};
+
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{ActivitySample, PlacementRate, TemplateProgress, UserCountSample};
+
+// Times are exported as milliseconds since the unix epoch.
+fn millis(time: SystemTime) -> u128 {
+	time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+}
+
+pub(crate) fn serialize_millis<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_u128(millis(*time))
+}
+
+// Something which can be written as rows of a CSV file.
+pub trait CsvRecord {
+	fn header() -> Vec<&'static str>;
+	// usually one row, but records holding several values may take more
+	fn rows(&self) -> Vec<Vec<String>>;
+}
+
+fn escape(field: &str) -> String {
+	if field.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_string()
+	}
+}
+
+fn write_row<W: Write>(writer: &mut W, fields: &[String]) -> io::Result<()> {
+	let line = fields.iter()
+		.map(|field| escape(field))
+		.collect::<Vec<_>>()
+		.join(",");
+	writeln!(writer, "{}", line)
+}
+
+// Writes a header followed by every record.
+pub fn write_csv<'a, W, R, I>(mut writer: W, records: I) -> io::Result<()>
+where
+	W: Write,
+	R: CsvRecord + 'a,
+	I: IntoIterator<Item = &'a R>,
+{
+	let header = R::header().into_iter().map(String::from).collect::<Vec<_>>();
+	write_row(&mut writer, &header)?;
+	for record in records {
+		for row in record.rows() {
+			write_row(&mut writer, &row)?;
+		}
+	}
+	writer.flush()
+}
+
+impl CsvRecord for ActivitySample {
+	fn header() -> Vec<&'static str> {
+		vec!["start", "count"]
+	}
+
+	fn rows(&self) -> Vec<Vec<String>> {
+		vec![vec![millis(self.start).to_string(), self.count.to_string()]]
+	}
+}
+
+impl CsvRecord for UserCountSample {
+	fn header() -> Vec<&'static str> {
+		vec!["time", "count"]
+	}
+
+	fn rows(&self) -> Vec<Vec<String>> {
+		vec![vec![millis(self.time).to_string(), self.count.to_string()]]
+	}
+}
+
+// A placement rate as it was at some time, see `Client::placement_rate`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlacementRateSample {
+	#[serde(serialize_with = "serialize_millis")]
+	pub time: SystemTime,
+	#[serde(flatten)]
+	pub rate: PlacementRate,
+}
+
+impl PlacementRateSample {
+	pub fn new(rate: PlacementRate) -> Self {
+		Self { time: SystemTime::now(), rate }
+	}
+}
+
+// One row for the whole board, with an empty region, then one per region.
+impl CsvRecord for PlacementRateSample {
+	fn header() -> Vec<&'static str> {
+		vec!["time", "region", "per_minute"]
+	}
+
+	fn rows(&self) -> Vec<Vec<String>> {
+		let time = millis(self.time).to_string();
+		let mut regions = self.rate.regions.iter().collect::<Vec<_>>();
+		regions.sort_by_key(|&(name, _)| name);
+
+		std::iter::once((String::new(), self.rate.per_minute))
+			.chain(regions.into_iter().map(|(name, &rate)| (name.clone(), rate)))
+			.map(|(region, rate)| vec![time.clone(), region, rate.to_string()])
+			.collect()
+	}
+}
+
+// A template's progress as it was at some time.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TemplateProgressSample {
+	#[serde(serialize_with = "serialize_millis")]
+	pub time: SystemTime,
+	pub template: String,
+	#[serde(flatten)]
+	pub progress: TemplateProgress,
+}
+
+impl TemplateProgressSample {
+	pub fn new<S: Into<String>>(template: S, progress: TemplateProgress) -> Self {
+		Self { time: SystemTime::now(), template: template.into(), progress }
+	}
+}
+
+impl CsvRecord for TemplateProgressSample {
+	fn header() -> Vec<&'static str> {
+		vec!["time", "template", "correct", "incorrect", "virgin", "completion"]
+	}
+
+	fn rows(&self) -> Vec<Vec<String>> {
+		vec![vec![
+			millis(self.time).to_string(),
+			self.template.clone(),
+			self.progress.correct.to_string(),
+			self.progress.incorrect.to_string(),
+			self.progress.virgin.to_string(),
+			self.progress.completion().to_string(),
+		]]
+	}
+}
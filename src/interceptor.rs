@@ -0,0 +1,16 @@
+use http::request::Parts;
+
+// Gets to inspect and change every request the client sends before it goes
+// out, including the websocket handshake. Only the head is available since
+// the websocket handshake has no body and REST request bodies are small
+// forms at most.
+pub trait RequestInterceptor: Send + Sync {
+	fn intercept(&self, request: &mut Parts);
+}
+
+impl<F> RequestInterceptor for F
+where F: Fn(&mut Parts) + Send + Sync {
+	fn intercept(&self, request: &mut Parts) {
+		self(request)
+	}
+}
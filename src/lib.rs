@@ -1,9 +1,86 @@
 mod messages;
 mod client;
 mod event_handler;
+mod client_set;
+mod position;
+mod emoji;
+mod chat_queue;
+mod placement_queue;
+mod snapshot;
+mod timelapse;
+mod activity;
+mod color_stats;
+mod compat;
+mod auth;
+mod rate_limit;
+mod disk_cache;
+mod lookup_cache;
+mod board;
+mod timestamps;
+mod reconnect;
+mod tls;
+mod interceptor;
+mod log_writer;
+mod chat_log;
+mod event_log;
+mod template;
+mod template_link;
+mod canvas_link;
+mod canvas_code;
+mod notify;
+mod user_state;
+mod placement_rate;
+mod user_count;
+mod export;
+mod dump;
+pub mod scan;
+mod resolve;
+mod connector;
+mod deflate;
+#[cfg(feature = "image")]
+mod detemplatize;
+
+#[cfg(feature = "commands")]
+pub mod commands;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
 pub use client::*;
+pub use client_set::ClientSet;
 pub use event_handler::EventHandler;
 pub use messages::*;
+pub use position::{Pos, OutOfBounds, Rect};
+pub use board::{AtomicBoard, BoardView};
+pub use timestamps::Timestamps;
+pub use emoji::{Emoji, EmojiSet};
+pub use snapshot::*;
+pub use timelapse::{Timelapse, Frames};
+pub use activity::{ActivityAggregator, ActivitySample, CanvasActivity};
+pub use color_stats::ColorTracker;
+pub use compat::{Capabilities, CompatProfile};
+pub use auth::{AuthError, Reauthenticator, Session, SignIn};
+pub use rate_limit::{Bandwidth, RateLimit, RateLimiter, RequestClass};
+pub use tls::Fingerprint;
+pub use interceptor::RequestInterceptor;
+pub use chat_log::ChatLog;
+pub use event_log::EventLog;
+pub use template::{Grief, Overlap, Template, TemplateError, TemplateProgress, TemplateSet, TemplateWatch};
+pub use template_link::{TemplateLink, TemplateLinkError};
+pub use canvas_link::{CanvasLink, CanvasLinkError};
+pub use canvas_code::CanvasCode;
+pub use user_state::UserState;
+pub use placement_rate::PlacementRate;
+pub use user_count::{UserCountSample, UserCountSummary};
+pub use dump::{Dump, DumpError};
+pub use export::{write_csv, CsvRecord, PlacementRateSample, TemplateProgressSample};
+pub use resolve::{AddressFamily, Resolver, StaticResolver, SystemResolver};
+pub use notify::{Notice, NotificationSink, NotifyError};
+#[cfg(feature = "webhook")]
+pub use notify::WebhookSink;
+#[cfg(feature = "image")]
+pub use template_link::TemplateLoadError;
+#[cfg(feature = "image")]
+pub use detemplatize::{detect_scale, detemplatize};
+pub use reconnect::{ExponentialBackoff, FixedDelay, Reconnect, ReconnectCause, ReconnectPolicy};
 
-
+pub use bytes::{Bytes, BytesMut};
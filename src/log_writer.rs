@@ -0,0 +1,132 @@
+use flate2::{Compression, write::GzEncoder};
+use serde_json::json;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::messages::Message;
+
+// A line holding the time the message was received in milliseconds and the
+// message itself.
+pub(crate) fn timestamped_line(message: &Message) -> io::Result<Vec<u8>> {
+	let time = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_millis() as u64;
+
+	let mut line = serde_json::to_vec(&json!({
+		"time": time,
+		"message": message,
+	}))?;
+	line.push(b'\n');
+	Ok(line)
+}
+
+// Appends lines to a file, moving it aside once it grows too large. Rotated
+// files are numbered from 1, newest first, so `chat.jsonl` becomes
+// `chat.jsonl.1` and the previous `chat.jsonl.1` becomes `chat.jsonl.2`.
+#[derive(Debug)]
+pub(crate) struct LogWriter {
+	path: PathBuf,
+	// rotation is off unless both are set
+	max_size: Option<u64>,
+	keep: usize,
+	// each line is written as its own gzip member, which decompresses to the
+	// same thing as compressing the whole file
+	compress: bool,
+	file: Option<File>,
+	size: u64,
+}
+
+impl LogWriter {
+	pub fn new(path: PathBuf) -> Self {
+		Self {
+			path,
+			max_size: None,
+			keep: 0,
+			compress: false,
+			file: None,
+			size: 0,
+		}
+	}
+
+	pub fn rotate(&mut self, max_size: u64, keep: usize) {
+		self.max_size = Some(max_size);
+		self.keep = keep;
+	}
+
+	pub fn compress(&mut self, compress: bool) {
+		self.compress = compress;
+	}
+
+	fn rotated_path(&self, index: usize) -> PathBuf {
+		let mut path = self.path.clone().into_os_string();
+		path.push(format!(".{}", index));
+		PathBuf::from(path)
+	}
+
+	async fn open(&mut self) -> io::Result<&mut File> {
+		if self.file.is_none() {
+			if let Some(parent) = self.path.parent() {
+				tokio::fs::create_dir_all(parent).await?;
+			}
+			let file = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(&self.path)
+				.await?;
+			self.size = file.metadata().await?.len();
+			self.file = Some(file);
+		}
+
+		Ok(self.file.as_mut().unwrap())
+	}
+
+	async fn rotate_files(&mut self) -> io::Result<()> {
+		self.file = None;
+
+		if self.keep == 0 {
+			return tokio::fs::remove_file(&self.path).await;
+		}
+
+		let _ = tokio::fs::remove_file(self.rotated_path(self.keep)).await;
+		for index in (1..self.keep).rev() {
+			let from = self.rotated_path(index);
+			if tokio::fs::metadata(&from).await.is_ok() {
+				tokio::fs::rename(from, self.rotated_path(index + 1)).await?;
+			}
+		}
+		tokio::fs::rename(&self.path, self.rotated_path(1)).await
+	}
+
+	pub async fn append(&mut self, line: &[u8]) -> io::Result<()> {
+		let compressed;
+		let line = if self.compress {
+			let mut encoder = GzEncoder::new(vec![], Compression::default());
+			encoder.write_all(line)?;
+			compressed = encoder.finish()?;
+			&compressed[..]
+		} else {
+			line
+		};
+
+		self.open().await?;
+
+		if let Some(max_size) = self.max_size {
+			let length = line.len() as u64;
+			if self.size > 0 && self.size + length > max_size {
+				self.rotate_files().await?;
+				self.open().await?;
+			}
+		}
+
+		let file = self.file.as_mut().unwrap();
+		file.write_all(line).await?;
+		file.flush().await?;
+		self.size += line.len() as u64;
+		Ok(())
+	}
+}
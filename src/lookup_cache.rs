@@ -0,0 +1,62 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Lookup, Pos};
+
+// Recent lookup results, evicting the least recently used once full. Results
+// are only valid until the pixel changes.
+#[derive(Debug)]
+pub(crate) struct LookupCache {
+	capacity: usize,
+	entries: HashMap<Pos, Option<Lookup>>,
+	// least recently used first
+	order: VecDeque<Pos>,
+}
+
+impl LookupCache {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			entries: HashMap::new(),
+			order: VecDeque::new(),
+		}
+	}
+
+	fn touch(&mut self, position: Pos) {
+		if let Some(index) = self.order.iter().position(|p| *p == position) {
+			self.order.remove(index);
+		}
+		self.order.push_back(position);
+	}
+
+	pub fn get(&mut self, position: Pos) -> Option<Option<Lookup>> {
+		let lookup = self.entries.get(&position).cloned()?;
+		self.touch(position);
+		Some(lookup)
+	}
+
+	pub fn insert(&mut self, position: Pos, lookup: Option<Lookup>) {
+		if self.capacity == 0 {
+			return;
+		}
+
+		self.entries.insert(position, lookup);
+		self.touch(position);
+
+		while self.order.len() > self.capacity {
+			if let Some(evicted) = self.order.pop_front() {
+				self.entries.remove(&evicted);
+			}
+		}
+	}
+
+	pub fn invalidate(&mut self, position: Pos) {
+		if self.entries.remove(&position).is_some() {
+			self.order.retain(|p| *p != position);
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.order.clear();
+	}
+}
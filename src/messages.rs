@@ -1,64 +1,161 @@
-use serde::{Deserialize, de::{Visitor, MapAccess}, Deserializer};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{Error, IgnoredAny, Visitor, MapAccess}, ser::{SerializeMap, SerializeStruct}};
+use serde_json::Value;
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{CanvasCode, Emoji, EmojiSet, Pos};
+
+// (De)serialized by hand as `{ "x", "y", "color" }` since flattening the
+// position would buffer every pixel, and pixels are by far the most common
+// thing the server sends.
+#[derive(Debug, Clone)]
 pub struct Pixel {
-	pub x: usize,
-	pub y: usize,
+	pub position: Pos,
 	pub color: u8,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum PixelField {
+	X,
+	Y,
+	Color,
+	#[serde(other)]
+	Other,
+}
+
+struct PixelVisitor;
+
+impl<'de> Visitor<'de> for PixelVisitor {
+	type Value = Pixel;
+
+	fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+		formatter.write_str("a pixel with x, y and color")
+	}
+
+	fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error> where M: MapAccess<'de> {
+		let mut x = None;
+		let mut y = None;
+		let mut color = None;
+
+		while let Some(key) = access.next_key()? {
+			match key {
+				PixelField::X => x = Some(access.next_value()?),
+				PixelField::Y => y = Some(access.next_value()?),
+				PixelField::Color => color = Some(access.next_value()?),
+				PixelField::Other => { access.next_value::<IgnoredAny>()?; },
+			}
+		}
+
+		let x = x.ok_or_else(|| M::Error::missing_field("x"))?;
+		let y = y.ok_or_else(|| M::Error::missing_field("y"))?;
+		let color = color.ok_or_else(|| M::Error::missing_field("color"))?;
+		Ok(Pixel { position: Pos::new(x, y), color })
+	}
+}
+
+impl<'de> Deserialize<'de> for Pixel {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where D: Deserializer<'de> {
+		deserializer.deserialize_struct("Pixel", &["x", "y", "color"], PixelVisitor)
+	}
+}
+
+impl Serialize for Pixel {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where S: Serializer {
+		let mut pixel = serializer.serialize_struct("Pixel", 3)?;
+		pixel.serialize_field("x", &self.position.x)?;
+		pixel.serialize_field("y", &self.position.y)?;
+		pixel.serialize_field("color", &self.color)?;
+		pixel.end()
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Notification {
-	id: usize,
-	time: u64,
-	expiry: Option<u64>,
-	who: String,
-	title: String,
-	content: String,
+	pub id: usize,
+	pub time: u64,
+	pub expiry: Option<u64>,
+	pub who: String,
+	pub title: String,
+	pub content: String,
+	#[serde(flatten)]
+	pub extra: HashMap<String, Value>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Purge {
-	initiator: String,
-	reason: String,
+	pub initiator: String,
+	pub reason: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Badge {
-	display_name: String,
-	tooltip: String,
-	css_icon: Option<String>,
+	pub display_name: String,
+	pub tooltip: String,
+	pub css_icon: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StrippedFaction {
-	id: usize,
-	name: String,
-	tag: Option<String>,
-	color: u32,
+	pub id: usize,
+	pub name: String,
+	pub tag: Option<String>,
+	pub color: u32,
+}
+
+// Faction colors are sent as 0xRRGGBB.
+fn faction_rgb(color: u32) -> [u8; 3] {
+	let [_, r, g, b] = color.to_be_bytes();
+	[r, g, b]
+}
+
+// In the same form as `Color::hex`.
+pub(crate) fn hex([r, g, b]: [u8; 3]) -> String {
+	format!("#{:02x}{:02x}{:02x}", r, g, b)
 }
 
-#[derive(Deserialize, Debug)]
+impl StrippedFaction {
+	pub fn rgb(&self) -> [u8; 3] {
+		faction_rgb(self.color)
+	}
+
+	pub fn hex(&self) -> String {
+		hex(self.rgb())
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatMessage {
-	id: u64,
-	author: String,
-	date: u64,
+	pub id: u64,
+	pub author: String,
+	pub date: u64,
 	#[serde(rename = "message_raw")]
-	message_raw: String,
-	purge: Option<Purge>,
-	badges: Vec<Badge>,
-	author_name_color: i32,
-	author_was_shadow_banned: Option<bool>,
-	stripped_faction: Option<StrippedFaction>,
+	pub message_raw: String,
+	pub purge: Option<Purge>,
+	pub badges: Vec<Badge>,
+	pub author_name_color: i32,
+	pub author_was_shadow_banned: Option<bool>,
+	pub stripped_faction: Option<StrippedFaction>,
+	#[serde(flatten)]
+	pub extra: HashMap<String, Value>,
 }
 
-#[derive(Debug)]
+impl ChatMessage {
+	pub fn custom_emoji<'a>(&'a self, emoji: &'a EmojiSet) -> Vec<&'a Emoji> {
+		emoji.find_in(&self.message_raw)
+	}
+}
+
+#[derive(Debug, Clone)]
 pub struct UserUpdate {
 	name_color: Option<isize>,
 	displayed_faction: Option<Option<UserFaction>>,
@@ -99,71 +196,194 @@ impl<'de> Deserialize<'de> for UserUpdate {
 	}
 }
 
-#[derive(Deserialize, Debug)]
+impl Serialize for UserUpdate {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where S: Serializer {
+		let mut map = serializer.serialize_map(None)?;
+		if let Some(name_color) = &self.name_color {
+			map.serialize_entry("NameColor", name_color)?;
+		}
+		if let Some(displayed_faction) = &self.displayed_faction {
+			map.serialize_entry("DisplayedFaction", displayed_faction)?;
+		}
+		map.end()
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct UserFaction {
-	id: usize,
-	color: u32,
-	name: String,
-	tag: String,
-	owner: String,
-	canvas_code: String,
+	pub id: usize,
+	pub color: u32,
+	pub name: String,
+	pub tag: String,
+	pub owner: String,
+	pub canvas_code: CanvasCode,
 	#[serde(rename = "creation_ms")]
-	creation_ms: u64,
-	member_count: usize,
-	user_joined: bool,
+	pub creation_ms: u64,
+	pub member_count: usize,
+	pub user_joined: bool,
+	#[serde(flatten)]
+	pub extra: HashMap<String, Value>,
 }
 
-#[derive(Deserialize, Debug)]
+impl UserFaction {
+	pub fn rgb(&self) -> [u8; 3] {
+		faction_rgb(self.color)
+	}
+
+	pub fn hex(&self) -> String {
+		hex(self.rgb())
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
-    id: usize,
-    stacked: usize,
-    chat_name_color: isize,
+	pub id: usize,
+	pub stacked: usize,
+	pub chat_name_color: isize,
 	#[serde(rename = "signup_time")]
-    signup_time: u64,
-    username: String,
-    cooldown_expiry: u64,
-    login_with_IP: bool,
-    signup_IP: String,
-    pixel_count: usize,
-    pixel_count_all_time: usize,
-    ban_expiry: Option<u64>,
-    is_perma_chatbanned: bool,
-    shadow_banned: bool,
-    chatban_expiry: u64,
-    is_rename_requested: bool,
-    discord_name: String,
-    chatban_reason: String,
-    displayed_faction: Option<usize>,
-    faction_blocked: Option<bool>,
-}
-
-#[derive(Deserialize, Debug)]
+	pub signup_time: u64,
+	pub username: String,
+	pub cooldown_expiry: u64,
+	#[serde(rename = "loginWithIP")]
+	login_with_ip: bool,
+	#[serde(rename = "signupIP")]
+	signup_ip: String,
+	pub pixel_count: usize,
+	pub pixel_count_all_time: usize,
+	pub ban_expiry: Option<u64>,
+	pub is_perma_chatbanned: bool,
+	pub shadow_banned: bool,
+	pub chatban_expiry: u64,
+	pub is_rename_requested: bool,
+	pub discord_name: String,
+	pub chatban_reason: String,
+	pub displayed_faction: Option<usize>,
+	pub faction_blocked: Option<bool>,
+	#[serde(flatten)]
+	pub extra: HashMap<String, Value>,
+}
+
+impl User {
+	// IP details are kept out of the public fields and Debug output so that
+	// they don't end up in logs by accident.
+	pub fn signup_ip(&self) -> &str {
+		&self.signup_ip
+	}
+
+	pub fn login_with_ip(&self) -> bool {
+		self.login_with_ip
+	}
+}
+
+impl std::fmt::Debug for User {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("User")
+			.field("id", &self.id)
+			.field("stacked", &self.stacked)
+			.field("chat_name_color", &self.chat_name_color)
+			.field("signup_time", &self.signup_time)
+			.field("username", &self.username)
+			.field("cooldown_expiry", &self.cooldown_expiry)
+			.field("login_with_ip", &"<redacted>")
+			.field("signup_ip", &"<redacted>")
+			.field("pixel_count", &self.pixel_count)
+			.field("pixel_count_all_time", &self.pixel_count_all_time)
+			.field("ban_expiry", &self.ban_expiry)
+			.field("is_perma_chatbanned", &self.is_perma_chatbanned)
+			.field("shadow_banned", &self.shadow_banned)
+			.field("chatban_expiry", &self.chatban_expiry)
+			.field("is_rename_requested", &self.is_rename_requested)
+			.field("discord_name", &self.discord_name)
+			.field("chatban_reason", &self.chatban_reason)
+			.field("displayed_faction", &self.displayed_faction)
+			.field("faction_blocked", &self.faction_blocked)
+			.field("extra", &self.extra)
+			.finish()
+	}
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ChatBanType {
+	Temp,
+	Perma,
+	Unban,
+	#[serde(other)]
+	Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatBan {
-	id: usize,
-	target: usize,
-	initiator: usize,
-	when: u64,
-	r#type: String,
-	expiry: u64,
-	reason: String,
-	purged: bool,
+	pub id: usize,
+	pub target: usize,
+	pub initiator: usize,
+	pub when: u64,
+	pub r#type: ChatBanType,
+	pub expiry: u64,
+	pub reason: String,
+	pub purged: bool,
 	#[serde(rename = "target_name")]
-	target_name: String,
+	pub target_name: String,
 	#[serde(rename = "initiator_name")]
-	initiator_name: String,
+	pub initiator_name: String,
+}
+
+// Durations sent as fractional seconds. Negative waits happen when the
+// cooldown has already ended by the time it's sent, so they become zero.
+mod seconds {
+	use serde::{Deserialize, Deserializer, Serializer};
+
+	use std::time::Duration;
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+		let seconds = f64::deserialize(deserializer)?;
+		Ok(Duration::try_from_secs_f64(seconds).unwrap_or_default())
+	}
+
+	pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_f64(duration.as_secs_f64())
+	}
+}
+
+fn millis_to_datetime(millis: u64) -> Option<DateTime<Utc>> {
+	Utc.timestamp_millis_opt(i64::try_from(millis).ok()?).single()
+}
+
+impl ChatBan {
+	pub fn issued_at(&self) -> Option<DateTime<Utc>> {
+		millis_to_datetime(self.when)
+	}
+
+	// None for bans which never expire.
+	pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+		match self.r#type {
+			ChatBanType::Perma => None,
+			_ => millis_to_datetime(self.expiry),
+		}
+	}
+
+	pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+		match self.r#type {
+			ChatBanType::Perma => true,
+			ChatBanType::Temp => self.expires_at().is_some_and(|expiry| expiry > now),
+			ChatBanType::Unban | ChatBanType::Unknown => false,
+		}
+	}
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum AcknowledgeType {
 	Place,
 	Undo,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PlacementOverrides {
 	ignore_cooldown: Option<bool>,
@@ -171,7 +391,22 @@ pub struct PlacementOverrides {
 	ignore_placemap: Option<bool>,
 }
 
-#[derive(Deserialize, Debug)]
+// Overrides which weren't sent aren't in effect.
+impl PlacementOverrides {
+	pub fn ignore_cooldown(&self) -> bool {
+		self.ignore_cooldown.unwrap_or(false)
+	}
+
+	pub fn can_place_any_color(&self) -> bool {
+		self.can_place_any_color.unwrap_or(false)
+	}
+
+	pub fn ignore_placemap(&self) -> bool {
+		self.ignore_placemap.unwrap_or(false)
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Role {
 	id: usize,
@@ -183,7 +418,7 @@ pub struct Role {
 	permissions: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum Message {
@@ -202,6 +437,8 @@ pub enum Message {
 	ChatBanState { permanent: bool, reason: String, expiry: u64 },
 	ChatPurge { target: String, initiator: String, amount: usize, reason: String, announce: bool },
 	ChatPurgeSpecific { target: String, initiator: String, IDs: Vec<usize>, reason: String, announce: bool },
+	// kept flat rather than flattening a `Pos`, which would buffer the message
+	// a second time, `EventHandler::handle_acknowledge` is given it as a `Pos`
 	#[serde(rename = "ACK")]
 	#[serde(rename_all = "camelCase")]
 	Acknowledge { ack_for: AcknowledgeType, x: usize, y: usize },
@@ -210,7 +447,7 @@ pub enum Message {
 	CaptchaRequired,
 	CaptchaStatus { success: bool },
 	CanUndo { time: u64 },
-	Cooldown { wait: f32 },
+	Cooldown { #[serde(with = "seconds")] wait: Duration },
 	ReceivedReport { report_id: usize, report_type: String },
 	Pixels { count: usize, cause: String },
 	#[serde(rename_all = "camelCase")]
@@ -238,4 +475,69 @@ pub enum Message {
 	Rename { requested: bool },
 	#[serde(rename_all = "camelCase")]
 	RenameSuccess { new_name: String },
-}
\ No newline at end of file
+}
+
+// Messages sent from the client to the server.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+	#[serde(rename = "pixel")]
+	Place(Pixel),
+	#[serde(rename = "undo")]
+	Undo,
+	#[serde(rename = "captcha")]
+	Captcha { token: String },
+	ChatMessage { message: String },
+	ChatHistory,
+	ChatbanState,
+	// mode is the kind of value arg holds, such as "username"
+	ChatLookup { arg: String, mode: String },
+	UserUpdate { updates: HashMap<String, Value> },
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+	Json(serde_json::Error),
+	// a packed pixel frame wasn't a whole number of pixels long
+	PackedLength(usize),
+	// packed pixels can't be placed on a board with no width
+	PackedWidth,
+}
+
+const PACKED_PIXEL_SIZE: usize = 5;
+
+impl Message {
+	// Parses a single websocket frame as sent by the server.
+	pub fn parse(text: &str) -> Result<Self, ParseError> {
+		serde_json::from_str(text).map_err(ParseError::Json)
+	}
+
+	// Decodes a binary pixel batch, sent by servers with the `binary_pixels`
+	// capability. Each pixel is packed into 5 bytes: its big endian index on
+	// the board followed by its color.
+	pub fn parse_packed(data: &[u8], width: usize) -> Result<Self, ParseError> {
+		if !data.len().is_multiple_of(PACKED_PIXEL_SIZE) {
+			return Err(ParseError::PackedLength(data.len()));
+		}
+		if width == 0 {
+			return Err(ParseError::PackedWidth);
+		}
+
+		let pixels = data.chunks_exact(PACKED_PIXEL_SIZE)
+			.map(|packed| {
+				let index = u32::from_be_bytes([packed[0], packed[1], packed[2], packed[3]]) as usize;
+				Pixel {
+					position: Pos::new(index % width, index / width),
+					color: packed[4],
+				}
+			})
+			.collect();
+
+		Ok(Message::Pixel { pixels })
+	}
+
+	// The reverse of `parse`, giving the frame the server would have sent.
+	pub fn to_json(&self) -> Result<String, serde_json::Error> {
+		serde_json::to_string(self)
+	}
+}
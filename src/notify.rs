@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+
+use crate::{CanvasCode, Grief, StatsMilestoneEntry};
+
+// Something an operator may want to hear about.
+#[derive(Debug, Clone)]
+pub enum Notice {
+	Alert { sender: String, message: String },
+	Milestone(StatsMilestoneEntry),
+	// the canvas code changed, so the board has been replaced
+	CanvasReset { old_code: CanvasCode, new_code: CanvasCode },
+	Grief { template: String, grief: Grief },
+}
+
+impl std::fmt::Display for Notice {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Notice::Alert { sender, message } => {
+				write!(f, "Alert from {}: {}", sender, message)
+			},
+			Notice::Milestone(milestone) => match milestone.user.as_ref() {
+				Some(user) => write!(f, "Pixel {} was placed by {}", milestone.pretty, user),
+				None => write!(f, "Pixel {} was placed", milestone.pretty),
+			},
+			Notice::CanvasReset { old_code, new_code } => {
+				write!(f, "Canvas {} has been replaced by canvas {}", old_code, new_code)
+			},
+			Notice::Grief { template, grief } => {
+				write!(f, "{} was griefed at {}", template, grief.position)?;
+				match grief.lookup.as_ref().and_then(|lookup| lookup.username.as_ref()) {
+					Some(username) => write!(f, " by {}", username),
+					None => Ok(()),
+				}
+			},
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum NotifyError {
+	Http(hyper::Error),
+	// the receiving end answered with something other than a success
+	Status(hyper::StatusCode),
+	Other(String),
+}
+
+// Somewhere notices are sent, such as a chat webhook. Failures are passed to
+// `handle_error` and the notice isn't retried.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+	async fn notify(&self, notice: &Notice) -> Result<(), NotifyError>;
+}
+
+#[cfg(feature = "webhook")]
+pub use webhook::WebhookSink;
+
+#[cfg(feature = "webhook")]
+mod webhook {
+	use async_trait::async_trait;
+	use hyper::client::HttpConnector;
+	use hyper::header;
+	use hyper_openssl::HttpsConnector;
+	use openssl::error::ErrorStack;
+	use url::Url;
+
+	use super::{Notice, NotificationSink, NotifyError};
+
+	// Posts each notice as a JSON object with the text under a single key,
+	// which is "content" by default as Discord expects.
+	pub struct WebhookSink {
+		url: Url,
+		key: String,
+		http_client: hyper::Client<HttpsConnector<HttpConnector>>,
+	}
+
+	impl WebhookSink {
+		pub fn new(url: Url) -> Result<Self, ErrorStack> {
+			let http_client = hyper::Client::builder()
+				.build(HttpsConnector::new()?);
+
+			Ok(Self { url, key: "content".to_string(), http_client })
+		}
+
+		// Slack style webhooks use "text".
+		pub fn key<S: Into<String>>(mut self, key: S) -> Self {
+			self.key = key.into();
+			self
+		}
+	}
+
+	#[async_trait]
+	impl NotificationSink for WebhookSink {
+		async fn notify(&self, notice: &Notice) -> Result<(), NotifyError> {
+			let body = serde_json::json!({ &self.key: notice.to_string() });
+			let request = hyper::Request::post(self.url.as_str())
+				.header(header::CONTENT_TYPE, "application/json")
+				.body(hyper::Body::from(body.to_string()))
+				.unwrap();
+
+			let response = self.http_client.request(request).await
+				.map_err(NotifyError::Http)?;
+
+			if response.status().is_success() {
+				Ok(())
+			} else {
+				Err(NotifyError::Status(response.status()))
+			}
+		}
+	}
+}
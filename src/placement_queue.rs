@@ -0,0 +1,71 @@
+use tokio::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::Pixel;
+
+// how long to wait for the server to respond to a placement before sending
+// the next one anyway
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Outgoing placements waiting to be sent. Only one placement is in flight at
+// a time so that the server's reply can set the next cooldown.
+#[derive(Debug, Default)]
+pub(crate) struct PlacementQueue {
+	pending: VecDeque<(Pixel, Instant)>,
+	in_flight: Option<Instant>,
+}
+
+impl PlacementQueue {
+	pub fn push(&mut self, pixel: Pixel) -> usize {
+		self.pending.push_back((pixel, Instant::now()));
+		self.pending.len()
+	}
+
+	pub fn len(&self) -> usize {
+		self.pending.len()
+	}
+
+	pub fn clear(&mut self) -> Vec<Pixel> {
+		self.pending.drain(..).map(|(pixel, _)| pixel).collect()
+	}
+
+	// Drops placements which have been waiting longer than `max_age`.
+	pub fn expire(&mut self, max_age: Duration) {
+		let now = Instant::now();
+		self.pending.retain(|(_, queued_at)| now.duration_since(*queued_at) < max_age);
+	}
+
+	// When the next placement should be sent given the current cooldown, if
+	// there is one.
+	pub fn due(&self, cooldown: Option<Instant>) -> Option<Instant> {
+		if self.pending.is_empty() {
+			None
+		} else {
+			let now = Instant::now();
+			let ready_at = self.in_flight.into_iter()
+				.map(|sent_at| sent_at + RESPONSE_TIMEOUT)
+				.chain(cooldown)
+				.fold(now, Instant::max);
+			Some(ready_at)
+		}
+	}
+
+	pub fn take(&mut self) -> Option<Pixel> {
+		let (pixel, _) = self.pending.pop_front()?;
+		self.in_flight = Some(Instant::now());
+		Some(pixel)
+	}
+
+	// Puts back a placement which could not be sent.
+	pub fn unsend(&mut self, pixel: Pixel) {
+		self.in_flight = None;
+		self.pending.push_front((pixel, Instant::now()));
+	}
+
+	// The server has replied to the last placement.
+	pub fn answered(&mut self) {
+		self.in_flight = None;
+	}
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Pixel, Rect};
+
+// Placement rates in pixels per minute, averaged over recent activity.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct PlacementRate {
+	pub per_minute: f64,
+	// keyed by the names given to `ClientBuidler::rate_region`
+	pub regions: HashMap<String, f64>,
+}
+
+// An exponentially weighted rate in pixels per second. Each placement adds
+// to the rate and its contribution decays away with the time constant, so
+// steady activity settles on the true rate.
+#[derive(Debug, Clone, Copy, Default)]
+struct Average {
+	rate: f64,
+	updated: Option<Instant>,
+}
+
+impl Average {
+	fn at(&self, now: Instant, time_constant: f64) -> f64 {
+		match self.updated {
+			Some(updated) => {
+				let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+				self.rate * (-elapsed / time_constant).exp()
+			},
+			None => 0.0,
+		}
+	}
+
+	fn add(&mut self, count: usize, now: Instant, time_constant: f64) {
+		self.rate = self.at(now, time_constant) + count as f64 / time_constant;
+		self.updated = Some(now);
+	}
+}
+
+#[derive(Debug)]
+pub(crate) struct RateTracker {
+	time_constant: f64,
+	regions: Vec<(String, Rect)>,
+	// the global average followed by one for each region
+	averages: Mutex<(Average, Vec<Average>)>,
+}
+
+impl RateTracker {
+	// `time_constant` must be non-zero, which `ClientBuidler::build` checks.
+	pub fn new(time_constant: Duration, regions: Vec<(String, Rect)>) -> Self {
+		let averages = vec![Average::default(); regions.len()];
+		Self {
+			time_constant: time_constant.as_secs_f64(),
+			regions,
+			averages: Mutex::new((Average::default(), averages)),
+		}
+	}
+
+	pub fn record(&self, pixels: &[Pixel]) {
+		let now = Instant::now();
+		let mut averages = self.averages.lock().unwrap();
+		averages.0.add(pixels.len(), now, self.time_constant);
+
+		for ((_, region), average) in self.regions.iter().zip(&mut averages.1) {
+			let count = pixels.iter()
+				.filter(|pixel| region.contains(pixel.position))
+				.count();
+			average.add(count, now, self.time_constant);
+		}
+	}
+
+	pub fn rate(&self) -> PlacementRate {
+		let now = Instant::now();
+		let averages = self.averages.lock().unwrap();
+		let per_minute = |average: &Average| average.at(now, self.time_constant) * 60.0;
+
+		PlacementRate {
+			per_minute: per_minute(&averages.0),
+			regions: self.regions.iter()
+				.zip(&averages.1)
+				.map(|((name, _), average)| (name.clone(), per_minute(average)))
+				.collect(),
+		}
+	}
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use crate::BoardInfo;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pos {
+	pub x: usize,
+	pub y: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+	pub position: Pos,
+	pub width: usize,
+	pub height: usize,
+}
+
+impl Pos {
+	pub fn new(x: usize, y: usize) -> Self {
+		Self { x, y }
+	}
+
+	pub fn checked(x: usize, y: usize, info: &BoardInfo) -> Result<Self, OutOfBounds> {
+		let position = Self::new(x, y);
+		if position.is_within(info.width, info.height) {
+			Ok(position)
+		} else {
+			Err(OutOfBounds { position, width: info.width, height: info.height })
+		}
+	}
+
+	pub fn is_within(&self, width: usize, height: usize) -> bool {
+		self.x < width && self.y < height
+	}
+
+	pub fn index(&self, width: usize) -> usize {
+		self.y * width + self.x
+	}
+
+	pub fn from_index(index: usize, width: usize) -> Self {
+		Self::new(index % width, index / width)
+	}
+}
+
+impl From<(usize, usize)> for Pos {
+	fn from((x, y): (usize, usize)) -> Self {
+		Self::new(x, y)
+	}
+}
+
+impl From<Pos> for (usize, usize) {
+	fn from(position: Pos) -> Self {
+		(position.x, position.y)
+	}
+}
+
+impl std::fmt::Display for Pos {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "({}, {})", self.x, self.y)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+}
+
+impl Rect {
+	pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+		Self { x, y, width, height }
+	}
+
+	pub fn from_corners(top_left: Pos, bottom_right: Pos) -> Self {
+		Self::new(
+			top_left.x,
+			top_left.y,
+			bottom_right.x.saturating_sub(top_left.x) + 1,
+			bottom_right.y.saturating_sub(top_left.y) + 1,
+		)
+	}
+
+	pub fn contains(&self, position: Pos) -> bool {
+		position.x >= self.x && position.x - self.x < self.width
+			&& position.y >= self.y && position.y - self.y < self.height
+	}
+
+	pub fn area(&self) -> usize {
+		self.width * self.height
+	}
+
+	pub fn positions(&self) -> impl Iterator<Item = Pos> {
+		let Self { x, y, width, height } = *self;
+		(y..y + height).flat_map(move |y| (x..x + width).map(move |x| Pos::new(x, y)))
+	}
+}
@@ -0,0 +1,177 @@
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use url::Url;
+
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+// Groups of REST endpoints which are limited together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestClass {
+	Info,
+	Buffer,
+	Stats,
+	Users,
+	Lookup,
+	Auth,
+	Other,
+}
+
+impl RequestClass {
+	pub fn of(location: &Url) -> Self {
+		let endpoint = location.path_segments()
+			.and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+			.unwrap_or_default();
+
+		match endpoint {
+			"info" => RequestClass::Info,
+			"boarddata" | "placemap" | "heatmap" | "virginmap" | "initialboarddata" => {
+				RequestClass::Buffer
+			},
+			"stats.json" => RequestClass::Stats,
+			"users" => RequestClass::Users,
+			"lookup" => RequestClass::Lookup,
+			_ if location.path().contains("/signin/") || location.path().contains("/auth/") => {
+				RequestClass::Auth
+			},
+			_ => RequestClass::Other,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+	pub requests: NonZeroUsize,
+	pub interval: Duration,
+}
+
+impl RateLimit {
+	// `None` if no requests are allowed at all, since nothing could ever be
+	// sent under such a limit.
+	pub fn new(requests: usize, interval: Duration) -> Option<Self> {
+		let requests = NonZeroUsize::new(requests)?;
+		Some(Self { requests, interval })
+	}
+}
+
+#[derive(Debug)]
+struct Window {
+	limit: RateLimit,
+	sent: Mutex<VecDeque<Instant>>,
+}
+
+impl Window {
+	// Forgets requests which are no longer within the interval.
+	fn prune(&self, sent: &mut VecDeque<Instant>, now: Instant) {
+		while sent.front().is_some_and(|at| now.duration_since(*at) >= self.limit.interval) {
+			sent.pop_front();
+		}
+	}
+}
+
+// Limits how many requests are made in each class over a sliding window. A
+// limiter can be shared between clients which talk to the same server.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+	windows: HashMap<RequestClass, Window>,
+}
+
+impl RateLimiter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn limit(mut self, class: RequestClass, limit: RateLimit) -> Self {
+		let window = Window { limit, sent: Mutex::new(VecDeque::new()) };
+		self.windows.insert(class, window);
+		self
+	}
+
+	// How many more requests of this class can be made right now, or `None`
+	// if the class isn't limited.
+	pub async fn budget(&self, class: RequestClass) -> Option<usize> {
+		let window = self.windows.get(&class)?;
+		let mut sent = window.sent.lock().await;
+		window.prune(&mut sent, Instant::now());
+		Some(window.limit.requests.get().saturating_sub(sent.len()))
+	}
+
+	// Waits until a request of this class is allowed and records it.
+	pub(crate) async fn acquire(&self, class: RequestClass) {
+		let window = match self.windows.get(&class) {
+			Some(window) => window,
+			None => return,
+		};
+
+		loop {
+			let now = Instant::now();
+			let mut sent = window.sent.lock().await;
+			window.prune(&mut sent, now);
+
+			if sent.len() < window.limit.requests.get() {
+				sent.push_back(now);
+				return;
+			}
+
+			let ready_at = sent.front().map(|at| *at + window.limit.interval);
+			drop(sent);
+			tokio::time::sleep_until(ready_at.unwrap_or(now)).await;
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+// Caps how fast buffer downloads are read, as a token bucket which allows
+// bursts of up to `burst` bytes. Like `RateLimiter`, it can be shared between
+// clients so that together they stay under the cap.
+#[derive(Debug)]
+pub struct Bandwidth {
+	bytes_per_second: u64,
+	burst: u64,
+	bucket: Mutex<Bucket>,
+}
+
+impl Bandwidth {
+	// Bursts default to one second's worth of bytes.
+	pub fn new(bytes_per_second: u64) -> Self {
+		let bytes_per_second = bytes_per_second.max(1);
+		Self {
+			bytes_per_second,
+			burst: bytes_per_second,
+			bucket: Mutex::new(Bucket { tokens: bytes_per_second as f64, updated: Instant::now() }),
+		}
+	}
+
+	pub fn burst(mut self, bytes: u64) -> Self {
+		self.burst = bytes.max(1);
+		self.bucket.get_mut().tokens = self.burst as f64;
+		self
+	}
+
+	pub fn bytes_per_second(&self) -> u64 {
+		self.bytes_per_second
+	}
+
+	// Waits until the bytes are allowed through. Chunks larger than the burst
+	// put the bucket into debt, which later reads wait out.
+	pub(crate) async fn consume(&self, bytes: usize) {
+		let deficit = {
+			let mut bucket = self.bucket.lock().await;
+			let now = Instant::now();
+			let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.bytes_per_second as f64;
+			bucket.tokens = (bucket.tokens + refilled).min(self.burst as f64) - bytes as f64;
+			bucket.updated = now;
+			-bucket.tokens
+		};
+
+		if deficit > 0.0 {
+			tokio::time::sleep(Duration::from_secs_f64(deficit / self.bytes_per_second as f64)).await;
+		}
+	}
+}
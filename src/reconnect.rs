@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use crate::{ConnectError, DisconnectReason};
+
+// Why the client is about to reconnect.
+#[derive(Debug)]
+pub enum ReconnectCause<'a> {
+	// the connection could not be established
+	Failed(&'a ConnectError),
+	// an established connection ended
+	Disconnected(&'a DisconnectReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconnect {
+	After(Duration),
+	GiveUp,
+}
+
+// Decides how long to wait before reconnecting. `attempt` counts the
+// reconnects since the last successful connection, starting at 1.
+pub trait ReconnectPolicy: Send + Sync {
+	fn reconnect(&self, cause: ReconnectCause, attempt: usize) -> Reconnect;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedDelay(pub Duration);
+
+impl ReconnectPolicy for FixedDelay {
+	fn reconnect(&self, _: ReconnectCause, _: usize) -> Reconnect {
+		Reconnect::After(self.0)
+	}
+}
+
+// Doubles the delay after every failed attempt, up to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+	pub initial: Duration,
+	pub max: Duration,
+	// give up after this many attempts in a row, if set
+	pub max_attempts: Option<usize>,
+}
+
+impl ReconnectPolicy for ExponentialBackoff {
+	fn reconnect(&self, _: ReconnectCause, attempt: usize) -> Reconnect {
+		if self.max_attempts.is_some_and(|max| attempt > max) {
+			return Reconnect::GiveUp;
+		}
+
+		let exponent = u32::try_from(attempt.saturating_sub(1)).unwrap_or(u32::MAX).min(31);
+		let delay = self.initial.saturating_mul(1 << exponent);
+		Reconnect::After(delay.min(self.max))
+	}
+}
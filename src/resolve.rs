@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use hyper::client::connect::dns::Name;
+use tokio::net::TcpStream;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+// How long a connection attempt gets before the next address is tried
+// alongside it, as recommended for happy eyeballs.
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// Which kinds of address to connect to. When both are allowed, attempts
+// alternate between the two, starting with the preferred one, so that a
+// broken network for one doesn't hold up the connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+	// whichever the resolver lists first
+	#[default]
+	Any,
+	PreferIpv4,
+	PreferIpv6,
+	Ipv4Only,
+	Ipv6Only,
+}
+
+impl AddressFamily {
+	// Drops addresses which aren't allowed and puts the rest in the order
+	// they should be tried.
+	fn order(self, addresses: Vec<IpAddr>) -> Vec<IpAddr> {
+		let prefer_ipv6 = match self {
+			AddressFamily::Any => addresses.first().is_some_and(IpAddr::is_ipv6),
+			AddressFamily::PreferIpv4 | AddressFamily::Ipv4Only => false,
+			AddressFamily::PreferIpv6 | AddressFamily::Ipv6Only => true,
+		};
+
+		let (preferred, mut other): (Vec<_>, Vec<_>) = addresses.into_iter()
+			.partition(|address| address.is_ipv6() == prefer_ipv6);
+		if matches!(self, AddressFamily::Ipv4Only | AddressFamily::Ipv6Only) {
+			other.clear();
+		}
+
+		let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+		let mut preferred = preferred.into_iter();
+		let mut other = other.into_iter();
+		loop {
+			match (preferred.next(), other.next()) {
+				(None, None) => return ordered,
+				(first, second) => ordered.extend(first.into_iter().chain(second)),
+			}
+		}
+	}
+}
+
+// Looks up the addresses of a host for both REST requests and the websocket.
+// Hosts which are already IP addresses aren't passed to it.
+#[async_trait]
+pub trait Resolver: Send + Sync {
+	async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>>;
+}
+
+// Uses the system's resolver, as when no resolver is set.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+	async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+		let addresses = tokio::net::lookup_host((host, 0)).await?;
+		Ok(addresses.map(|address| address.ip()).collect())
+	}
+}
+
+// Answers for some hosts from a fixed table, like a hosts file, and passes
+// everything else on to another resolver.
+pub struct StaticResolver {
+	hosts: HashMap<String, Vec<IpAddr>>,
+	fallback: Arc<dyn Resolver>,
+}
+
+impl Default for StaticResolver {
+	fn default() -> Self {
+		Self {
+			hosts: HashMap::new(),
+			fallback: Arc::new(SystemResolver),
+		}
+	}
+}
+
+impl StaticResolver {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// Can be given several times for the same host to add more addresses.
+	pub fn host<S: Into<String>>(mut self, host: S, address: IpAddr) -> Self {
+		let host = host.into().to_ascii_lowercase();
+		self.hosts.entry(host).or_default().push(address);
+		self
+	}
+
+	pub fn fallback<R: Resolver + 'static>(mut self, resolver: R) -> Self {
+		self.fallback = Arc::new(resolver);
+		self
+	}
+}
+
+#[async_trait]
+impl Resolver for StaticResolver {
+	async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+		match self.hosts.get(&host.to_ascii_lowercase()) {
+			Some(addresses) => Ok(addresses.clone()),
+			None => self.fallback.resolve(host).await,
+		}
+	}
+}
+
+// Resolves a host, not calling the resolver for IP addresses. The addresses
+// are in the order they should be tried.
+pub(crate) async fn lookup(
+	resolver: &dyn Resolver,
+	family: AddressFamily,
+	host: &str,
+	port: u16,
+) -> io::Result<Vec<SocketAddr>> {
+	let addresses = match host.parse::<IpAddr>() {
+		Ok(address) => vec![address],
+		Err(_) => resolver.resolve(host).await?,
+	};
+	let addresses = family.order(addresses);
+
+	if addresses.is_empty() {
+		let message = format!("no addresses found for {}", host);
+		return Err(io::Error::new(io::ErrorKind::NotFound, message));
+	}
+
+	Ok(addresses.into_iter().map(|address| SocketAddr::new(address, port)).collect())
+}
+
+// Connects to the first address that answers. Each attempt gets a short
+// head start before the next address is tried alongside it, and a failed
+// attempt moves on to the next address straight away.
+pub(crate) async fn connect(addresses: Vec<SocketAddr>) -> io::Result<TcpStream> {
+	let mut remaining = addresses.into_iter();
+	let mut attempts = FuturesUnordered::new();
+	let mut error = None;
+
+	loop {
+		if attempts.is_empty() {
+			match remaining.next() {
+				Some(address) => attempts.push(TcpStream::connect(address)),
+				None => {
+					let error = error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to"));
+					return Err(error);
+				},
+			}
+		}
+
+		tokio::select! {
+			Some(result) = attempts.next() => match result {
+				Ok(stream) => return Ok(stream),
+				Err(failed) => {
+					error = Some(failed);
+					if let Some(address) = remaining.next() {
+						attempts.push(TcpStream::connect(address));
+					}
+				},
+			},
+			_ = tokio::time::sleep(ATTEMPT_DELAY), if remaining.len() > 0 => {
+				attempts.push(TcpStream::connect(remaining.next().unwrap()));
+			},
+		}
+	}
+}
+
+// Adapts a `Resolver` to what hyper's connector expects. Hyper does its own
+// happy eyeballs with the addresses in the order given.
+#[derive(Clone)]
+pub(crate) struct HyperResolver {
+	pub resolver: Arc<dyn Resolver>,
+	pub family: AddressFamily,
+}
+
+impl hyper::service::Service<Name> for HyperResolver {
+	type Response = std::vec::IntoIter<SocketAddr>;
+	type Error = io::Error;
+	type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, name: Name) -> Self::Future {
+		let resolver = self.resolver.clone();
+		let family = self.family;
+		Box::pin(async move {
+			// hyper fills in the port itself
+			lookup(resolver.as_ref(), family, name.as_str(), 0).await
+				.map(Vec::into_iter)
+		})
+	}
+}
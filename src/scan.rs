@@ -0,0 +1,56 @@
+// Whole-board passes, which are spread across threads with the rayon feature.
+// The client uses them on fetched buffers, but they work on any board.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use std::time::{Duration, SystemTime};
+
+use crate::{Pixel, Pos};
+
+// Seconds since the canvas started at which each pixel was last placed, or 0
+// for virgin pixels. The heatmap holds how many seconds ago recently placed
+// pixels were placed. Pixels which would have been placed before the canvas
+// started are given 0.
+pub fn timestamps(
+	heatmap: &[u8],
+	virginmap: &[u8],
+	now: SystemTime,
+	canvas_start: SystemTime,
+) -> Vec<u32> {
+	let timestamp = |(&heat, &virgin): (&u8, &u8)| {
+		if virgin == 0 {
+			// pixel is non-virgin
+			let pixel_time = now - Duration::from_secs(u64::from(heat));
+			let since_start = pixel_time.duration_since(canvas_start).unwrap_or_default();
+			// 136 years is a pretty long time
+			u32::try_from(since_start.as_secs()).unwrap_or(u32::MAX)
+		} else {
+			// pixel is virgin
+			0
+		}
+	};
+
+	#[cfg(feature = "rayon")]
+	return heatmap.par_iter().zip(virginmap).map(timestamp).collect();
+
+	#[cfg(not(feature = "rayon"))]
+	return heatmap.iter().zip(virginmap).map(timestamp).collect();
+}
+
+// Pixels whose color differs between the two boards, with their color in
+// `current`.
+pub fn changed(current: &[u8], previous: &[u8], width: usize) -> Vec<Pixel> {
+	let difference = |(index, (&color, &previous)): (usize, (&u8, &u8))| {
+		(color != previous).then(|| Pixel {
+			position: Pos::from_index(index, width),
+			color,
+		})
+	};
+
+	#[cfg(feature = "rayon")]
+	return current.par_iter().zip(previous).enumerate().filter_map(difference).collect();
+
+	#[cfg(not(feature = "rayon"))]
+	return current.iter().zip(previous).enumerate().filter_map(difference).collect();
+}
@@ -0,0 +1,172 @@
+use bytes::Bytes;
+use futures_util::StreamExt;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{CanvasCode, Client, Color, RequestError};
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+	pub canvas_code: CanvasCode,
+	pub width: usize,
+	pub height: usize,
+	pub palette: Vec<Color>,
+	pub colors: Bytes,
+	pub taken_at: SystemTime,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+	Request(RequestError),
+	Io(std::io::Error),
+	#[cfg(feature = "image")]
+	Image(image::ImageError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+	// palette indices, one byte per pixel
+	Raw,
+	#[cfg(feature = "image")]
+	Png,
+}
+
+impl SnapshotFormat {
+	fn extension(&self) -> &'static str {
+		match self {
+			SnapshotFormat::Raw => "bin",
+			#[cfg(feature = "image")]
+			SnapshotFormat::Png => "png",
+		}
+	}
+}
+
+impl Snapshot {
+	pub async fn capture(client: &Client) -> Result<Self, RequestError> {
+		client.snapshot_colors().await
+	}
+
+	// Resolves palette indices to colors. Pixels without a palette entry
+	// (such as those outside of the canvas shape) are transparent.
+	pub fn rgba(&self) -> Vec<u8> {
+		self.colors.iter()
+			.flat_map(|&index| match self.palette.get(usize::from(index)) {
+				Some(color) => [color.value[0], color.value[1], color.value[2], u8::MAX],
+				None => [0; 4],
+			})
+			.collect()
+	}
+
+	#[cfg(feature = "image")]
+	pub fn to_image(&self) -> image::RgbaImage {
+		let width = u32::try_from(self.width).unwrap();
+		let height = u32::try_from(self.height).unwrap();
+		image::RgbaImage::from_raw(width, height, self.rgba())
+			.expect("Snapshot buffer does not match its dimensions")
+	}
+
+	pub fn file_name(&self, format: SnapshotFormat) -> String {
+		let timestamp = self.taken_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+		format!(
+			"{}_{}.{}",
+			self.canvas_code,
+			timestamp.as_millis(),
+			format.extension(),
+		)
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P, format: SnapshotFormat) -> Result<(), SnapshotError> {
+		match format {
+			SnapshotFormat::Raw => std::fs::write(path, &self.colors)
+				.map_err(SnapshotError::Io),
+			#[cfg(feature = "image")]
+			SnapshotFormat::Png => self.to_image().save(path)
+				.map_err(SnapshotError::Image),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotTrigger {
+	Interval(Duration),
+	PixelsChanged(usize),
+}
+
+pub enum SnapshotTarget {
+	Callback(Box<dyn Fn(Snapshot) + Send + Sync>),
+	Directory { path: PathBuf, format: SnapshotFormat },
+}
+
+// Periodically captures snapshots of a client's board. Run it alongside the
+// client's connection loop, e.g. with `tokio::join!`.
+pub struct Snapshots {
+	trigger: SnapshotTrigger,
+	target: SnapshotTarget,
+}
+
+impl Snapshots {
+	pub fn new(trigger: SnapshotTrigger, target: SnapshotTarget) -> Self {
+		Self { trigger, target }
+	}
+
+	pub fn every(interval: Duration, target: SnapshotTarget) -> Self {
+		Self::new(SnapshotTrigger::Interval(interval), target)
+	}
+
+	pub fn every_pixels(count: usize, target: SnapshotTarget) -> Self {
+		Self::new(SnapshotTrigger::PixelsChanged(count), target)
+	}
+
+	pub fn to_directory<P: Into<PathBuf>>(trigger: SnapshotTrigger, path: P, format: SnapshotFormat) -> Self {
+		Self::new(trigger, SnapshotTarget::Directory { path: path.into(), format })
+	}
+
+	pub fn to_callback<F>(trigger: SnapshotTrigger, callback: F) -> Self
+	where F: Fn(Snapshot) + Send + Sync + 'static {
+		Self::new(trigger, SnapshotTarget::Callback(Box::new(callback)))
+	}
+
+	// Captures snapshots until an error occurs.
+	pub async fn run(&self, client: &Client) -> Result<(), SnapshotError> {
+		match self.trigger {
+			SnapshotTrigger::Interval(interval) => {
+				let mut ticks = tokio::time::interval(interval);
+				loop {
+					ticks.tick().await;
+					self.take(client).await?;
+				}
+			},
+			SnapshotTrigger::PixelsChanged(count) => {
+				let pixels = client.pixels().chunks(count.max(1));
+				futures_util::pin_mut!(pixels);
+				while pixels.next().await.is_some() {
+					self.take(client).await?;
+				}
+				Ok(())
+			},
+		}
+	}
+
+	async fn take(&self, client: &Client) -> Result<(), SnapshotError> {
+		let snapshot = Snapshot::capture(client).await
+			.map_err(SnapshotError::Request)?;
+
+		match &self.target {
+			SnapshotTarget::Callback(callback) => callback(snapshot),
+			SnapshotTarget::Directory { path, format } => {
+				std::fs::create_dir_all(path).map_err(SnapshotError::Io)?;
+				let file = path.join(snapshot.file_name(*format));
+				snapshot.save(file, *format)?;
+			},
+		}
+
+		Ok(())
+	}
+}
+
+impl std::fmt::Debug for Snapshots {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Snapshots").field("trigger", &self.trigger).finish()
+	}
+}
@@ -0,0 +1,421 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+use crate::{BoardView, Lookup, Pixel, Pos, Rect};
+use crate::client::UNPLACEABLE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateError {
+	// there wasn't one entry for each pixel of the template
+	Size { expected: usize, actual: usize },
+}
+
+// Artwork to be kept on the board, as palette indices with its top left
+// corner at `origin`. Pixels which are `None` aren't part of the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+	pub name: String,
+	origin: Pos,
+	width: usize,
+	height: usize,
+	pixels: Vec<Option<u8>>,
+	// higher is fixed first, indexed like the pixels
+	priorities: Vec<i32>,
+}
+
+impl Template {
+	pub fn new<S: Into<String>>(
+		name: S,
+		origin: Pos,
+		width: usize,
+		height: usize,
+		pixels: Vec<Option<u8>>,
+	) -> Result<Self, TemplateError> {
+		let expected = width * height;
+		if pixels.len() != expected {
+			return Err(TemplateError::Size { expected, actual: pixels.len() });
+		}
+
+		Ok(Self {
+			name: name.into(),
+			origin,
+			width,
+			height,
+			priorities: vec![0; pixels.len()],
+			pixels,
+		})
+	}
+
+	// Sets the priority of the template pixels within a region of the board.
+	// Everything starts at 0, and later calls override earlier ones where
+	// they overlap.
+	pub fn with_priority(mut self, region: Rect, priority: i32) -> Self {
+		for position in region.positions() {
+			if let Some(index) = self.local_index(position) {
+				self.priorities[index] = priority;
+			}
+		}
+		self
+	}
+
+	// Like `with_priority` but for the template pixels where the mask, which
+	// has one entry per template pixel, is set.
+	pub fn with_priority_mask(mut self, mask: &[bool], priority: i32) -> Result<Self, TemplateError> {
+		if mask.len() != self.pixels.len() {
+			return Err(TemplateError::Size { expected: self.pixels.len(), actual: mask.len() });
+		}
+
+		for (index, _) in mask.iter().enumerate().filter(|(_, &masked)| masked) {
+			self.priorities[index] = priority;
+		}
+		Ok(self)
+	}
+
+	fn local_index(&self, position: Pos) -> Option<usize> {
+		if !self.rect().contains(position) {
+			return None;
+		}
+
+		let local = Pos::new(position.x - self.origin.x, position.y - self.origin.y);
+		Some(local.index(self.width))
+	}
+
+	pub fn priority(&self, position: Pos) -> Option<i32> {
+		self.local_index(position).map(|index| self.priorities[index])
+	}
+
+	pub fn origin(&self) -> Pos {
+		self.origin
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	// The area of the board the template covers.
+	pub fn rect(&self) -> Rect {
+		Rect::new(self.origin.x, self.origin.y, self.width, self.height)
+	}
+
+	// The color the template wants at a board position.
+	pub fn get(&self, position: Pos) -> Option<u8> {
+		self.local_index(position).and_then(|index| self.pixels[index])
+	}
+
+	// Every board position the template covers along with the color it wants
+	// there.
+	pub fn pixels(&self) -> impl Iterator<Item = (Pos, u8)> + '_ {
+		self.pixels.iter()
+			.enumerate()
+			.filter_map(move |(index, color)| {
+				let local = Pos::from_index(index, self.width);
+				let position = Pos::new(self.origin.x + local.x, self.origin.y + local.y);
+				color.map(|color| (position, color))
+			})
+	}
+
+	fn prioritized_pixels(&self) -> impl Iterator<Item = (Pos, u8, i32)> + '_ {
+		self.pixels()
+			.map(|(position, color)| (position, color, self.priority(position).unwrap_or_default()))
+	}
+
+	// The placements needed to complete the template, highest priority first
+	// and otherwise from top to bottom. Pixels outside the board or on
+	// unplaceable pixels are skipped.
+	pub fn mismatches(&self, board: &BoardView) -> impl Iterator<Item = Pixel> {
+		let mut mismatches = self.pixels.iter()
+			.zip(&self.priorities)
+			.enumerate()
+			.filter_map(|(index, (wanted, &priority))| {
+				let wanted = (*wanted)?;
+				let local = Pos::from_index(index, self.width);
+				let position = Pos::new(self.origin.x + local.x, self.origin.y + local.y);
+				let color = board.get(position.x, position.y)?;
+				(color != wanted && color != UNPLACEABLE).then_some((priority, Pixel { position, color: wanted }))
+			})
+			.collect::<Vec<_>>();
+
+		// stable, so equal priorities keep their order
+		mismatches.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+		mismatches.into_iter().map(|(_, pixel)| pixel)
+	}
+}
+
+// Which template decides a pixel's color where several overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlap {
+	// the template which was added first
+	#[default]
+	First,
+	// the template which was added last
+	Last,
+	// the template with the higher priority for that pixel, then the one
+	// added first
+	Priority,
+}
+
+// A collection of templates on the same canvas, each of which can be turned
+// off without removing it. Templates are identified by name.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateSet {
+	templates: Vec<(Template, bool)>,
+	overlap: Overlap,
+}
+
+impl TemplateSet {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn overlap(mut self, overlap: Overlap) -> Self {
+		self.overlap = overlap;
+		self
+	}
+
+	// Adds an enabled template, returning any template it replaced. A
+	// replaced template keeps its place in the order.
+	pub fn add(&mut self, template: Template) -> Option<Template> {
+		match self.templates.iter_mut().find(|(existing, _)| existing.name == template.name) {
+			Some(entry) => Some(std::mem::replace(entry, (template, true)).0),
+			None => {
+				self.templates.push((template, true));
+				None
+			},
+		}
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<Template> {
+		let index = self.templates.iter().position(|(template, _)| template.name == name)?;
+		Some(self.templates.remove(index).0)
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Template> {
+		self.templates.iter()
+			.find(|(template, _)| template.name == name)
+			.map(|(template, _)| template)
+	}
+
+	// Returns whether there was a template with the name.
+	pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+		match self.templates.iter_mut().find(|(template, _)| template.name == name) {
+			Some(entry) => {
+				entry.1 = enabled;
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn is_enabled(&self, name: &str) -> bool {
+		self.templates.iter().any(|(template, enabled)| *enabled && template.name == name)
+	}
+
+	pub fn len(&self) -> usize {
+		self.templates.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.templates.is_empty()
+	}
+
+	// Every template in the order they were added, whether or not enabled.
+	pub fn templates(&self) -> impl Iterator<Item = &Template> {
+		self.templates.iter().map(|(template, _)| template)
+	}
+
+	pub fn enabled(&self) -> impl Iterator<Item = &Template> {
+		self.templates.iter()
+			.filter(|(_, enabled)| *enabled)
+			.map(|(template, _)| template)
+	}
+
+	// The color and priority wanted at each position covered by an enabled
+	// template, after resolving overlaps.
+	fn resolve(&self) -> HashMap<Pos, (u8, i32)> {
+		let mut wanted = HashMap::new();
+
+		for template in self.enabled() {
+			for (position, color, priority) in template.prioritized_pixels() {
+				let replace = match (wanted.get(&position), self.overlap) {
+					(None, _) => true,
+					(Some(_), Overlap::First) => false,
+					(Some(_), Overlap::Last) => true,
+					(Some(&(_, existing)), Overlap::Priority) => priority > existing,
+				};
+
+				if replace {
+					wanted.insert(position, (color, priority));
+				}
+			}
+		}
+
+		wanted
+	}
+
+	// The color wanted at a position once overlaps are resolved.
+	pub fn get_color(&self, position: Pos) -> Option<u8> {
+		self.resolve().get(&position).map(|&(color, _)| color)
+	}
+
+	// The placements needed to complete every enabled template, ordered like
+	// `Template::mismatches`.
+	pub fn mismatches(&self, board: &BoardView) -> impl Iterator<Item = Pixel> {
+		let mut mismatches = self.resolve().into_iter()
+			.filter_map(|(position, (wanted, priority))| {
+				let color = board.get(position.x, position.y)?;
+				(color != wanted && color != UNPLACEABLE).then_some((priority, Pixel { position, color: wanted }))
+			})
+			.collect::<Vec<_>>();
+
+		mismatches.sort_by_key(|(priority, pixel)| {
+			(std::cmp::Reverse(*priority), pixel.position.y, pixel.position.x)
+		});
+		mismatches.into_iter().map(|(_, pixel)| pixel)
+	}
+}
+
+// How much of a template is on the board. Template pixels outside the board
+// or on unplaceable pixels aren't counted.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TemplateProgress {
+	pub correct: usize,
+	pub incorrect: usize,
+	// wrong, but only because nobody has placed there yet
+	pub virgin: usize,
+}
+
+impl TemplateProgress {
+	pub fn total(&self) -> usize {
+		self.correct + self.incorrect + self.virgin
+	}
+
+	// The fraction of the template which is correct, from 0 to 1.
+	pub fn completion(&self) -> f64 {
+		match self.total() {
+			0 => 1.0,
+			total => self.correct as f64 / total as f64,
+		}
+	}
+
+	// Whether going from this progress to `other` passes any of the
+	// completion thresholds, in either direction.
+	pub fn crosses(&self, other: &TemplateProgress, thresholds: &[f64]) -> bool {
+		let (before, after) = (self.completion(), other.completion());
+		thresholds.iter().any(|&threshold| (before < threshold) != (after < threshold))
+	}
+}
+
+// What `Client::watch_template` reports on.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateWatch {
+	// completion fractions from 0 to 1 which call `handle_template_progress`
+	// when passed
+	pub thresholds: Vec<f64>,
+	// look up who placed each griefing pixel before reporting it, which costs
+	// a request per pixel
+	pub lookup_griefers: bool,
+}
+
+// A correct template pixel being replaced with the wrong color.
+#[derive(Debug, Clone)]
+pub struct Grief {
+	pub position: Pos,
+	pub old_color: u8,
+	pub new_color: u8,
+	// only looked up when `lookup_griefers` is set, and `None` if that failed
+	pub lookup: Option<Lookup>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+	Correct,
+	Incorrect,
+	Virgin,
+}
+
+// Keeps a template's progress up to date from individual placements, without
+// going back to the whole board each time.
+#[derive(Debug)]
+pub(crate) struct TemplateTracker {
+	template: Template,
+	// indexed like the template's pixels, `None` where nothing is counted
+	cells: Vec<Option<Cell>>,
+	progress: TemplateProgress,
+}
+
+impl TemplateTracker {
+	pub fn new(template: Template, board: &BoardView, virginmap: &[u8]) -> Self {
+		let mut progress = TemplateProgress::default();
+
+		let cells = template.pixels.iter()
+			.enumerate()
+			.map(|(index, wanted)| {
+				let wanted = (*wanted)?;
+				let local = Pos::from_index(index, template.width);
+				let position = Pos::new(template.origin.x + local.x, template.origin.y + local.y);
+				let color = board.get(position.x, position.y)
+					.filter(|&color| color != UNPLACEABLE)?;
+
+				let cell = if color == wanted {
+					progress.correct += 1;
+					Cell::Correct
+				} else if virginmap.get(position.index(board.width())).is_some_and(|&virgin| virgin != 0) {
+					progress.virgin += 1;
+					Cell::Virgin
+				} else {
+					progress.incorrect += 1;
+					Cell::Incorrect
+				};
+
+				Some(cell)
+			})
+			.collect();
+
+		Self { template, cells, progress }
+	}
+
+	pub fn template(&self) -> &Template {
+		&self.template
+	}
+
+	pub fn progress(&self) -> TemplateProgress {
+		self.progress
+	}
+
+	// Updates the progress for a placement, returning the grief if it undid
+	// a correct pixel.
+	pub fn apply(&mut self, pixel: &Pixel) -> Option<Grief> {
+		let wanted = self.template.get(pixel.position)?;
+
+		let origin = self.template.origin;
+		let local = Pos::new(pixel.position.x - origin.x, pixel.position.y - origin.y);
+		let cell = self.cells[local.index(self.template.width)].as_mut()?;
+		let was_correct = *cell == Cell::Correct;
+
+		match cell {
+			Cell::Correct => self.progress.correct -= 1,
+			Cell::Incorrect => self.progress.incorrect -= 1,
+			Cell::Virgin => self.progress.virgin -= 1,
+		}
+
+		if pixel.color == wanted {
+			self.progress.correct += 1;
+			*cell = Cell::Correct;
+			None
+		} else {
+			self.progress.incorrect += 1;
+			*cell = Cell::Incorrect;
+			was_correct.then_some(Grief {
+				position: pixel.position,
+				old_color: wanted,
+				new_color: pixel.color,
+				lookup: None,
+			})
+		}
+	}
+}
@@ -0,0 +1,139 @@
+use url::Url;
+
+use crate::Pos;
+#[cfg(feature = "image")]
+use crate::detemplatize::{detect_scale, detemplatize};
+#[cfg(feature = "image")]
+use crate::{Color, RequestError, Template, TemplateError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateLinkError {
+	// the link has no `template` parameter
+	MissingImage,
+	InvalidImage(url::ParseError),
+	// a coordinate or width parameter wasn't a non-negative integer
+	InvalidNumber { key: String, value: String },
+}
+
+// The template part of a canvas link, as shared from the site's template
+// settings: `#template=<image>&tlx=<x>&tly=<y>&tw=<width>&title=<title>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateLink {
+	pub image: Url,
+	// where the top left corner of the image goes on the board
+	pub origin: Pos,
+	// the width the image is shown at in board pixels, if it differs from
+	// the width of the image itself
+	pub width: Option<usize>,
+	pub title: Option<String>,
+}
+
+impl TemplateLink {
+	// Relative image locations are resolved against the link itself.
+	pub fn parse(link: &Url) -> Result<Self, TemplateLinkError> {
+		let fragment = link.fragment().unwrap_or_default();
+		let parameters = url::form_urlencoded::parse(fragment.as_bytes())
+			.collect::<Vec<_>>();
+
+		let get = |keys: &[&str]| {
+			keys.iter().find_map(|key| {
+				parameters.iter()
+					.find(|(name, _)| name == key)
+					.map(|(_, value)| value.to_string())
+			})
+		};
+
+		let number = |keys: &[&str]| {
+			get(keys)
+				.filter(|value| !value.is_empty())
+				.map(|value| value.parse::<usize>()
+					.map_err(|_| TemplateLinkError::InvalidNumber { key: keys[0].to_string(), value }))
+				.transpose()
+		};
+
+		let image = get(&["template"])
+			.filter(|image| !image.is_empty())
+			.ok_or(TemplateLinkError::MissingImage)?;
+		let image = link.join(&image)
+			.map_err(TemplateLinkError::InvalidImage)?;
+
+		// older links use ox and oy for the origin
+		let x = number(&["tlx", "ox"])?.unwrap_or_default();
+		let y = number(&["tly", "oy"])?.unwrap_or_default();
+
+		Ok(Self {
+			image,
+			origin: Pos::new(x, y),
+			width: number(&["tw"])?,
+			title: get(&["title"]).filter(|title| !title.is_empty()),
+		})
+	}
+}
+
+impl std::str::FromStr for TemplateLink {
+	type Err = TemplateLinkError;
+
+	fn from_str(link: &str) -> Result<Self, Self::Err> {
+		let link = Url::parse(link).map_err(TemplateLinkError::InvalidImage)?;
+		Self::parse(&link)
+	}
+}
+
+#[cfg(feature = "image")]
+#[derive(Debug)]
+pub enum TemplateLoadError {
+	Request(RequestError),
+	Image(image::ImageError),
+	// the image width isn't a whole multiple of the width it's shown at
+	Scale { image_width: usize, width: usize },
+	Template(TemplateError),
+}
+
+#[cfg(feature = "image")]
+impl TemplateLink {
+	// Converts a downloaded template image to palette indices. Styled images
+	// are reduced to one pixel per board pixel first, using the link's width
+	// to find the scale if it has one. Transparent pixels aren't part of the
+	// template and anything else becomes the closest palette color.
+	pub fn to_template(&self, image: &[u8], palette: &[Color]) -> Result<Template, TemplateLoadError> {
+		let image = image::load_from_memory(image)
+			.map_err(TemplateLoadError::Image)?
+			.into_rgba8();
+		let image_width = image.width() as usize;
+
+		let scale = match self.width {
+			Some(width) if width > 0 && image_width.is_multiple_of(width) => (image_width / width) as u32,
+			Some(width) => return Err(TemplateLoadError::Scale { image_width, width }),
+			None => detect_scale(&image),
+		};
+		let image = detemplatize(&image, scale)
+			.ok_or(TemplateLoadError::Scale { image_width, width: image_width / scale as usize })?;
+		let width = image.width() as usize;
+		let height = image.height() as usize;
+
+		let pixels = image.pixels()
+			.map(|pixel| nearest_color(pixel.0, palette))
+			.collect();
+
+		let name = self.title.clone().unwrap_or_else(|| self.image.to_string());
+		Template::new(name, self.origin, width, height, pixels)
+			.map_err(TemplateLoadError::Template)
+	}
+}
+
+#[cfg(feature = "image")]
+fn nearest_color([r, g, b, a]: [u8; 4], palette: &[Color]) -> Option<u8> {
+	if a < u8::MAX / 2 {
+		return None;
+	}
+
+	palette.iter()
+		.enumerate()
+		.min_by_key(|(_, color)| {
+			let [pr, pg, pb] = color.value;
+			[(r, pr), (g, pg), (b, pb)].iter()
+				.map(|&(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+				.sum::<i32>()
+		})
+		.and_then(|(index, _)| u8::try_from(index).ok())
+}
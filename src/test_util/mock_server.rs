@@ -0,0 +1,408 @@
+use bytes::{Buf, Bytes, BytesMut};
+use flate2::{Compress, Compression, FlushCompress};
+use futures_util::{SinkExt, StreamExt};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use hyper::header;
+use hyper::service::{make_service_fn, service_fn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{broadcast, oneshot};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message as WebsocketMessage;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use url::Url;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+use crate::deflate::{self, FIN, OPCODE, RSV1};
+
+#[derive(Debug, Clone)]
+pub enum ScriptStep {
+	Send(String),
+	Wait(Duration),
+	Close,
+}
+
+#[derive(Default)]
+struct MockState {
+	routes: HashMap<String, Bytes>,
+	script: Vec<ScriptStep>,
+	compress: bool,
+	requests: Mutex<Vec<String>>,
+	received: Mutex<Vec<String>>,
+}
+
+// Builds an in-process pxls server which serves fixed REST responses and
+// plays a script of websocket messages to every connecting client.
+#[derive(Default)]
+pub struct MockServerBuilder {
+	routes: HashMap<String, Bytes>,
+	script: Vec<ScriptStep>,
+	compress: bool,
+}
+
+impl MockServerBuilder {
+	pub fn route<B: Into<Bytes>>(mut self, path: &str, body: B) -> Self {
+		let path = format!("/{}", path.trim_start_matches('/'));
+		self.routes.insert(path, body.into());
+		self
+	}
+
+	pub fn info<S: Into<String>>(self, json: S) -> Self {
+		self.route("info", json.into())
+	}
+
+	pub fn stats<S: Into<String>>(self, json: S) -> Self {
+		self.route("stats/stats.json", json.into())
+	}
+
+	pub fn users(self, count: usize) -> Self {
+		self.route("users", format!(r#"{{"type":"users","count":{}}}"#, count))
+	}
+
+	pub fn colors<B: Into<Bytes>>(self, buffer: B) -> Self {
+		self.route("boarddata", buffer)
+	}
+
+	pub fn initial_colors<B: Into<Bytes>>(self, buffer: B) -> Self {
+		self.route("initialboarddata", buffer)
+	}
+
+	pub fn mask<B: Into<Bytes>>(self, buffer: B) -> Self {
+		self.route("placemap", buffer)
+	}
+
+	pub fn heatmap<B: Into<Bytes>>(self, buffer: B) -> Self {
+		self.route("heatmap", buffer)
+	}
+
+	pub fn virginmap<B: Into<Bytes>>(self, buffer: B) -> Self {
+		self.route("virginmap", buffer)
+	}
+
+	pub fn send<S: Into<String>>(mut self, message: S) -> Self {
+		self.script.push(ScriptStep::Send(message.into()));
+		self
+	}
+
+	pub fn wait(mut self, duration: Duration) -> Self {
+		self.script.push(ScriptStep::Wait(duration));
+		self
+	}
+
+	pub fn close(mut self) -> Self {
+		self.script.push(ScriptStep::Close);
+		self
+	}
+
+	// Accepts permessage-deflate from clients which ask for it and then
+	// compresses every message sent to them.
+	pub fn compress(mut self) -> Self {
+		self.compress = true;
+		self
+	}
+
+	pub fn start(self) -> std::io::Result<MockServer> {
+		let listener = TcpListener::bind("127.0.0.1:0")?;
+		listener.set_nonblocking(true)?;
+		let address = listener.local_addr()?;
+
+		let state = Arc::new(MockState {
+			routes: self.routes,
+			script: self.script,
+			compress: self.compress,
+			..Default::default()
+		});
+		let (pushes, _) = broadcast::channel(1024);
+		let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+
+		let service_state = state.clone();
+		let service_pushes = pushes.clone();
+		let make_service = make_service_fn(move |_| {
+			let state = service_state.clone();
+			let pushes = service_pushes.clone();
+			async move {
+				Ok::<_, Infallible>(service_fn(move |request| {
+					serve(state.clone(), pushes.clone(), request)
+				}))
+			}
+		});
+
+		let server = Server::from_tcp(listener)
+			.map_err(std::io::Error::other)?
+			.serve(make_service)
+			.with_graceful_shutdown(async {
+				shutdown_signal.await.ok();
+			});
+
+		tokio::spawn(server);
+
+		Ok(MockServer {
+			address,
+			state,
+			pushes,
+			shutdown: Some(shutdown),
+		})
+	}
+}
+
+pub struct MockServer {
+	address: SocketAddr,
+	state: Arc<MockState>,
+	pushes: broadcast::Sender<String>,
+	shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+	pub fn builder() -> MockServerBuilder {
+		MockServerBuilder::default()
+	}
+
+	pub fn address(&self) -> SocketAddr {
+		self.address
+	}
+
+	pub fn url(&self) -> Url {
+		Url::parse(&format!("http://{}/", self.address)).unwrap()
+	}
+
+	// Sends a message to every currently connected websocket.
+	pub fn push<S: Into<String>>(&self, message: S) -> usize {
+		self.pushes.send(message.into()).unwrap_or(0)
+	}
+
+	// Paths of every HTTP request the server has handled, in order.
+	pub fn requests(&self) -> Vec<String> {
+		self.state.requests.lock().unwrap().clone()
+	}
+
+	// Text frames sent to the server by connected clients, in order.
+	pub fn received(&self) -> Vec<String> {
+		self.state.received.lock().unwrap().clone()
+	}
+}
+
+impl std::fmt::Debug for MockServer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("MockServer").field("address", &self.address).finish()
+	}
+}
+
+impl Drop for MockServer {
+	fn drop(&mut self) {
+		if let Some(shutdown) = self.shutdown.take() {
+			shutdown.send(()).ok();
+		}
+	}
+}
+
+async fn serve(
+	state: Arc<MockState>,
+	pushes: broadcast::Sender<String>,
+	request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+	let path = request.uri().path().to_owned();
+	state.requests.lock().unwrap().push(path.clone());
+
+	if path == "/ws" {
+		return Ok(upgrade(state, pushes, request));
+	}
+
+	let response = match state.routes.get(&path) {
+		Some(body) => Response::new(Body::from(body.clone())),
+		None => Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.body(Body::empty())
+			.unwrap(),
+	};
+
+	Ok(response)
+}
+
+fn upgrade(
+	state: Arc<MockState>,
+	pushes: broadcast::Sender<String>,
+	request: Request<Body>,
+) -> Response<Body> {
+	let key = match request.headers().get(header::SEC_WEBSOCKET_KEY) {
+		Some(key) => derive_accept_key(key.as_bytes()),
+		None => {
+			return Response::builder()
+				.status(StatusCode::BAD_REQUEST)
+				.body(Body::empty())
+				.unwrap();
+		},
+	};
+
+	let compress = state.compress && request.headers()
+		.get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.any(|extension| extension.split(';').next().unwrap().trim() == "permessage-deflate");
+
+	// subscribe before responding so that no pushes are missed
+	let pushes = pushes.subscribe();
+	tokio::spawn(async move {
+		if let Ok(upgraded) = hyper::upgrade::on(request).await {
+			if compress {
+				let socket = WebSocketStream::from_raw_socket(Deflate::new(upgraded), Role::Server, None).await;
+				run_socket(state, pushes, socket).await;
+			} else {
+				let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+				run_socket(state, pushes, socket).await;
+			}
+		}
+	});
+
+	let mut response = Response::builder()
+		.status(StatusCode::SWITCHING_PROTOCOLS)
+		.header(header::CONNECTION, "upgrade")
+		.header(header::UPGRADE, "websocket")
+		.header(header::SEC_WEBSOCKET_ACCEPT, key);
+	if compress {
+		response = response.header(header::SEC_WEBSOCKET_EXTENSIONS, "permessage-deflate");
+	}
+	response.body(Body::empty()).unwrap()
+}
+
+async fn run_socket<S: AsyncRead + AsyncWrite + Unpin>(
+	state: Arc<MockState>,
+	mut pushes: broadcast::Receiver<String>,
+	socket: WebSocketStream<S>,
+) {
+	let (mut write, mut read) = socket.split();
+
+	for step in &state.script {
+		let result = match step {
+			ScriptStep::Send(message) => {
+				write.send(WebsocketMessage::Text(message.clone())).await
+			},
+			ScriptStep::Wait(duration) => {
+				tokio::time::sleep(*duration).await;
+				Ok(())
+			},
+			ScriptStep::Close => {
+				write.send(WebsocketMessage::Close(None)).await.ok();
+				return;
+			},
+		};
+
+		if result.is_err() {
+			return;
+		}
+	}
+
+	loop {
+		tokio::select! {
+			push = pushes.recv() => match push {
+				Ok(message) => {
+					if write.send(WebsocketMessage::Text(message)).await.is_err() {
+						return;
+					}
+				},
+				Err(broadcast::error::RecvError::Lagged(_)) => (),
+				Err(broadcast::error::RecvError::Closed) => return,
+			},
+			frame = read.next() => match frame {
+				Some(Ok(WebsocketMessage::Text(text))) => {
+					state.received.lock().unwrap().push(text);
+				},
+				Some(Ok(WebsocketMessage::Close(_))) | Some(Err(_)) | None => return,
+				Some(Ok(_)) => (),
+			},
+		}
+	}
+}
+
+// Compresses the data frames tungstenite writes, keeping the compression
+// window between messages as clients have to expect by default.
+struct Deflate<T> {
+	inner: T,
+	compress: Compress,
+	// written by tungstenite and not yet a whole frame
+	pending: BytesMut,
+	// compressed and waiting to be written
+	out: BytesMut,
+}
+
+impl<T> Deflate<T> {
+	fn new(inner: T) -> Self {
+		Self {
+			inner,
+			compress: Compress::new(Compression::default(), false),
+			pending: BytesMut::new(),
+			out: BytesMut::new(),
+		}
+	}
+
+	fn process(&mut self) -> io::Result<()> {
+		while let Some(header) = deflate::parse_header(&self.pending)? {
+			if self.pending.len() < header.length + header.payload {
+				return Ok(());
+			}
+
+			let first = self.pending[0];
+			let opcode = first & OPCODE;
+			if first & FIN == 0 || !matches!(opcode, 0x1 | 0x2) {
+				let frame = self.pending.split_to(header.length + header.payload);
+				self.out.extend_from_slice(&frame);
+				continue;
+			}
+
+			self.pending.advance(header.length);
+			let payload = self.pending.split_to(header.payload);
+			let mut compressed = Vec::with_capacity(payload.len() + 64);
+			let start_in = self.compress.total_in();
+			loop {
+				let consumed = (self.compress.total_in() - start_in) as usize;
+				self.compress.compress_vec(&payload[consumed..], &mut compressed, FlushCompress::Sync)
+					.map_err(io::Error::other)?;
+				if compressed.len() < compressed.capacity() {
+					break;
+				}
+				compressed.reserve(compressed.capacity());
+			}
+			compressed.truncate(compressed.len() - deflate::MESSAGE_TAIL.len());
+			deflate::write_frame(&mut self.out, FIN | RSV1 | opcode, &compressed);
+		}
+
+		Ok(())
+	}
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Deflate<T> {
+	fn poll_read(self: Pin<&mut Self>, context: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.get_mut().inner).poll_read(context, buf)
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Deflate<T> {
+	fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		let this = self.get_mut();
+		this.pending.extend_from_slice(buf);
+		this.process()?;
+		Poll::Ready(Ok(buf.len()))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		let this = self.get_mut();
+		while !this.out.is_empty() {
+			let written = ready!(Pin::new(&mut this.inner).poll_write(context, &this.out))?;
+			this.out.advance(written);
+		}
+		Pin::new(&mut this.inner).poll_flush(context)
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		ready!(self.as_mut().poll_flush(context))?;
+		Pin::new(&mut self.get_mut().inner).poll_shutdown(context)
+	}
+}
@@ -0,0 +1,5 @@
+mod mock_server;
+mod recording_handler;
+
+pub use mock_server::*;
+pub use recording_handler::*;
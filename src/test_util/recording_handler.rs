@@ -0,0 +1,490 @@
+use async_trait::async_trait;
+use tokio::sync::Notify;
+use url::Url;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{BoardInfo, CacheKind, Client, ClientError, ClientWarning, DisconnectReason, EventHandler, Grief, Pos, StatsMilestoneEntry, Template, TemplateProgress};
+use crate::messages::{
+	AcknowledgeType, ChatBan, ChatMessage, Message, Notification,
+	Pixel, PlacementOverrides, Role, User, UserFaction, UserUpdate,
+};
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum RecordedEvent {
+	Ready,
+	Disconnect(DisconnectReason),
+	Message(Message),
+	Milestone(StatsMilestoneEntry),
+	CacheRefreshed(CacheKind),
+	BoardResize { old: (usize, usize), new: (usize, usize) },
+	RateLimited { location: Url, retry_after: Duration },
+	TemplateProgress { name: String, progress: TemplateProgress },
+	Grief { name: String, grief: Grief },
+	Raw(String),
+	Unknown(String),
+	// errors aren't cloneable, so only their debug output is kept
+	Error(String),
+	Warning(String),
+}
+
+#[derive(Default)]
+struct Recording {
+	events: Mutex<Vec<RecordedEvent>>,
+	notify: Notify,
+}
+
+// An event handler which records every call it receives. Clones share the
+// same recording, so one can be given to the client and the other inspected.
+#[derive(Default, Clone)]
+pub struct RecordingHandler {
+	recording: Arc<Recording>,
+}
+
+impl RecordingHandler {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn events(&self) -> Vec<RecordedEvent> {
+		self.recording.events.lock().unwrap().clone()
+	}
+
+	pub fn messages(&self) -> Vec<Message> {
+		self.events().into_iter()
+			.filter_map(|event| match event {
+				RecordedEvent::Message(message) => Some(message),
+				_ => None,
+			})
+			.collect()
+	}
+
+	pub fn len(&self) -> usize {
+		self.recording.events.lock().unwrap().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn clear(&self) {
+		self.recording.events.lock().unwrap().clear();
+	}
+
+	// Resolves with the first recorded event matching the predicate, waiting
+	// for new events if none has been recorded yet.
+	pub async fn wait_for<F>(&self, predicate: F) -> RecordedEvent
+	where F: Fn(&RecordedEvent) -> bool {
+		loop {
+			let notified = self.recording.notify.notified();
+
+			let found = self.recording.events.lock().unwrap()
+				.iter()
+				.find(|event| predicate(event))
+				.cloned();
+
+			if let Some(event) = found {
+				return event;
+			}
+
+			notified.await;
+		}
+	}
+
+	pub async fn wait_for_ready(&self) {
+		self.wait_for(|event| matches!(event, RecordedEvent::Ready)).await;
+	}
+
+	pub async fn wait_for_disconnect(&self) {
+		self.wait_for(|event| matches!(event, RecordedEvent::Disconnect(_))).await;
+	}
+
+	pub async fn wait_for_pixel<P: Into<Pos>>(&self, position: P) -> Pixel {
+		let position = position.into();
+		let find_pixel = |event: &RecordedEvent| match event {
+			RecordedEvent::Message(Message::Pixel { pixels }) => {
+				pixels.iter().rev().find(|p| p.position == position).cloned()
+			},
+			_ => None,
+		};
+
+		let event = self.wait_for(|event| find_pixel(event).is_some()).await;
+		find_pixel(&event).unwrap()
+	}
+
+	fn record(&self, event: RecordedEvent) {
+		self.recording.events.lock().unwrap().push(event);
+		self.recording.notify.notify_waiters();
+	}
+
+	fn record_message(&self, message: Message) {
+		self.record(RecordedEvent::Message(message))
+	}
+}
+
+impl std::fmt::Debug for RecordingHandler {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("RecordingHandler").field("events", &self.len()).finish()
+	}
+}
+
+#[async_trait]
+impl EventHandler for RecordingHandler {
+	async fn handle_ready(&self, _: &Client, _: &BoardInfo) {
+		self.record(RecordedEvent::Ready)
+	}
+
+	async fn handle_disconnect(&self, _: &Client, reason: DisconnectReason) {
+		self.record(RecordedEvent::Disconnect(reason))
+	}
+
+	async fn handle_acknowledge(
+		&self,
+		_: &Client,
+		ack_for: AcknowledgeType,
+		position: Pos,
+	) {
+		self.record_message(Message::Acknowledge { ack_for, x: position.x, y: position.y })
+	}
+
+	async fn handle_overrides(
+		&self,
+		_: &Client,
+		placement_overrides: PlacementOverrides,
+	) {
+		self.record_message(Message::AdminPlacementOverrides { placement_overrides })
+	}
+
+	async fn handle_alert(
+		&self,
+		_: &Client,
+		sender: String,
+		message: String,
+	) {
+		self.record_message(Message::Alert { sender, message })
+	}
+
+	async fn handle_can_undo(
+		&self,
+		_: &Client,
+		time: u64,
+	) {
+		self.record_message(Message::CanUndo { time })
+	}
+
+	async fn handle_captcha_status(
+		&self,
+		_: &Client,
+		success: bool,
+	) {
+		self.record_message(Message::CaptchaStatus { success })
+	}
+
+	async fn handle_captcha_required(
+		&self,
+		_: &Client,
+	) {
+		self.record_message(Message::CaptchaRequired)
+	}
+
+	async fn handle_chatban(
+		&self,
+		_: &Client,
+		permanent: bool,
+		reason: String,
+		expiry: u64,
+	) {
+		self.record_message(Message::ChatBan { permanent, reason, expiry })
+	}
+
+	async fn handle_chatban_state(
+		&self,
+		_: &Client,
+		permanent: bool,
+		reason: String,
+		expiry: u64,
+	) {
+		self.record_message(Message::ChatBanState { permanent, reason, expiry })
+	}
+
+	async fn handle_chat_history(
+		&self,
+		_: &Client,
+		messages: Vec<ChatMessage>,
+	) {
+		self.record_message(Message::ChatHistory { messages })
+	}
+
+	async fn handle_chat_lookup(
+		&self,
+		_: &Client,
+		target: User,
+		history: Vec<ChatMessage>,
+		chatbans: Vec<ChatBan>,
+	) {
+		self.record_message(Message::ChatLookup { target, history, chatbans })
+	}
+
+	async fn handle_chat_message(
+		&self,
+		_: &Client,
+		message: ChatMessage,
+	) {
+		self.record_message(Message::ChatMessage { message })
+	}
+
+	async fn handle_chat_purge(
+		&self,
+		_: &Client,
+		target: String,
+		initiator: String,
+		amount: usize,
+		reason: String,
+		announce: bool,
+	) {
+		self.record_message(Message::ChatPurge { target, initiator, amount, reason, announce })
+	}
+
+	async fn handle_chat_purge_specific(
+		&self,
+		_: &Client,
+		target: String,
+		initiator: String,
+		ids: Vec<usize>,
+		reason: String,
+		announce: bool,
+	) {
+		self.record_message(Message::ChatPurgeSpecific { target, initiator, IDs: ids, reason, announce })
+	}
+
+	async fn handle_chat_user_update(
+		&self,
+		_: &Client,
+		who: String,
+		updates: UserUpdate,
+	) {
+		self.record_message(Message::ChatUserUpdate { who, updates })
+	}
+
+	async fn handle_cooldown(
+		&self,
+		_: &Client,
+		wait: Duration,
+	) {
+		self.record_message(Message::Cooldown { wait })
+	}
+
+	async fn handle_faction_clear(
+		&self,
+		_: &Client,
+		fid: usize,
+	) {
+		self.record_message(Message::FactionClear { fid })
+	}
+
+	async fn handle_faction_update(
+		&self,
+		_: &Client,
+		faction: UserFaction,
+	) {
+		self.record_message(Message::FactionUpdate { faction })
+	}
+
+	async fn handle_message_cooldown(
+		&self,
+		_: &Client,
+		diff: usize,
+		message: String,
+	) {
+		self.record_message(Message::MessageCooldown { diff, message })
+	}
+
+	async fn handle_notification(
+		&self,
+		_: &Client,
+		notification: Notification,
+	) {
+		self.record_message(Message::Notification { notification })
+	}
+
+	async fn handle_board_update(
+		&self,
+		_: &Client,
+		pixels: Vec<Pixel>,
+	) {
+		self.record_message(Message::Pixel { pixels })
+	}
+
+	async fn handle_pixel_counts(
+		&self,
+		_: &Client,
+		pixel_count: usize,
+		pixel_count_all_time: usize,
+	) {
+		self.record_message(Message::PixelCounts { pixel_count, pixel_count_all_time })
+	}
+
+	async fn handle_pixels_available(
+		&self,
+		_: &Client,
+		count: usize,
+		cause: String,
+	) {
+		self.record_message(Message::Pixels { count, cause })
+	}
+
+	async fn handle_received_report(
+		&self,
+		_: &Client,
+		report_id: usize,
+		report_type: String,
+	) {
+		self.record_message(Message::ReceivedReport { report_id, report_type })
+	}
+
+	async fn handle_rename(
+		&self,
+		_: &Client,
+		requested: bool,
+	) {
+		self.record_message(Message::Rename { requested })
+	}
+
+	async fn handle_rename_success(
+		&self,
+		_: &Client,
+		new_name: String,
+	) {
+		self.record_message(Message::RenameSuccess { new_name })
+	}
+
+	async fn handle_user_info(
+		&self,
+		_: &Client,
+		username: String,
+		roles: Vec<Role>,
+		pixel_count: usize,
+		pixel_count_all_time: usize,
+		banned: bool,
+		ban_expiry: Option<u64>,
+		ban_reason: Option<String>,
+		method: String,
+		placement_overrides: PlacementOverrides,
+		chat_banned: bool,
+		chatban_reason: Option<String>,
+		chatban_is_perma: Option<bool>,
+		chatban_expiry: Option<u64>,
+		rename_requested: bool,
+		discord_name: Option<String>,
+		chat_name_color: isize,
+	) {
+		self.record_message(Message::Userinfo {
+			username,
+			roles,
+			pixel_count,
+			pixel_count_all_time,
+			banned,
+			ban_expiry,
+			ban_reason,
+			method,
+			placement_overrides,
+			chat_banned,
+			chatban_reason,
+			chatban_is_perma,
+			chatban_expiry,
+			rename_requested,
+			discord_name,
+			chat_name_color,
+		})
+	}
+
+	async fn handle_user_count(
+		&self,
+		_: &Client,
+		count: usize,
+	) {
+		self.record_message(Message::Users { count })
+	}
+
+	async fn handle_board_resize(
+		&self,
+		_: &Client,
+		old: (usize, usize),
+		new: (usize, usize),
+	) {
+		self.record(RecordedEvent::BoardResize { old, new })
+	}
+
+	async fn handle_cache_refreshed(&self, _: &Client, kind: CacheKind) {
+		self.record(RecordedEvent::CacheRefreshed(kind))
+	}
+
+	async fn handle_milestone(
+		&self,
+		_: &Client,
+		milestone: StatsMilestoneEntry,
+	) {
+		self.record(RecordedEvent::Milestone(milestone))
+	}
+
+	async fn handle_template_progress(
+		&self,
+		_: &Client,
+		template: &Template,
+		progress: TemplateProgress,
+	) {
+		self.record(RecordedEvent::TemplateProgress { name: template.name.clone(), progress })
+	}
+
+	async fn handle_grief(
+		&self,
+		_: &Client,
+		template: &Template,
+		grief: Grief,
+	) {
+		self.record(RecordedEvent::Grief { name: template.name.clone(), grief })
+	}
+
+	async fn handle_rate_limited(
+		&self,
+		_: &Client,
+		location: Url,
+		retry_after: Duration,
+	) {
+		self.record(RecordedEvent::RateLimited { location, retry_after })
+	}
+
+	async fn handle_raw(
+		&self,
+		_: &Client,
+		text: &str,
+	) {
+		self.record(RecordedEvent::Raw(text.to_owned()))
+	}
+
+	async fn handle_unknown(
+		&self,
+		_: &Client,
+		packet: String,
+	) {
+		self.record(RecordedEvent::Unknown(packet))
+	}
+
+	async fn handle_error(
+		&self,
+		_: &Client,
+		error: ClientError,
+	) {
+		self.record(RecordedEvent::Error(format!("{:?}", error)))
+	}
+
+	async fn handle_warning(
+		&self,
+		_: &Client,
+		warning: ClientWarning,
+	) {
+		self.record(RecordedEvent::Warning(format!("{:?}", warning)))
+	}
+}
@@ -0,0 +1,96 @@
+use bytes::Bytes;
+
+use std::iter::Peekable;
+use std::time::{Duration, SystemTime};
+
+use crate::{Pixel, Snapshot};
+
+// Replays a log of timed pixel placements over a starting snapshot,
+// producing a snapshot of the board every `step`.
+#[derive(Debug, Clone)]
+pub struct Timelapse {
+	start: Snapshot,
+	step: Duration,
+}
+
+impl Timelapse {
+	// Returns None if `step` is zero, since the frames would never advance.
+	pub fn new(start: Snapshot, step: Duration) -> Option<Self> {
+		(!step.is_zero()).then_some(Self { start, step })
+	}
+
+	pub fn frames<I>(self, pixels: I) -> Frames<I::IntoIter>
+	where I: IntoIterator<Item = (SystemTime, Pixel)> {
+		Frames {
+			colors: self.start.colors.to_vec(),
+			next_frame: self.start.taken_at + self.step,
+			start: self.start,
+			step: self.step,
+			pixels: pixels.into_iter().peekable(),
+			finished: false,
+		}
+	}
+
+	// Writes every frame as a numbered PNG (`frame_000000.png`, …) suitable
+	// for passing to ffmpeg. Returns the number of frames written.
+	#[cfg(feature = "image")]
+	pub fn write_frames<I, P>(self, pixels: I, directory: P) -> Result<usize, crate::SnapshotError>
+	where
+		I: IntoIterator<Item = (SystemTime, Pixel)>,
+		P: AsRef<std::path::Path>,
+	{
+		let directory = directory.as_ref();
+		std::fs::create_dir_all(directory).map_err(crate::SnapshotError::Io)?;
+
+		let mut count = 0;
+		for (index, frame) in self.frames(pixels).enumerate() {
+			let path = directory.join(format!("frame_{:06}.png", index));
+			frame.save(path, crate::SnapshotFormat::Png)?;
+			count += 1;
+		}
+
+		Ok(count)
+	}
+}
+
+pub struct Frames<I: Iterator<Item = (SystemTime, Pixel)>> {
+	start: Snapshot,
+	step: Duration,
+	colors: Vec<u8>,
+	next_frame: SystemTime,
+	pixels: Peekable<I>,
+	finished: bool,
+}
+
+impl<I: Iterator<Item = (SystemTime, Pixel)>> Iterator for Frames<I> {
+	type Item = Snapshot;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished {
+			return None;
+		}
+
+		let frame_time = self.next_frame;
+		while let Some((time, pixel)) = self.pixels.next_if(|(time, _)| *time < frame_time) {
+			// placements from before the starting snapshot are already in it
+			if time < self.start.taken_at {
+				continue;
+			}
+
+			if pixel.position.is_within(self.start.width, self.start.height) {
+				let index = pixel.position.index(self.start.width);
+				self.colors[index] = pixel.color;
+			}
+		}
+
+		// the final frame includes everything left in the log
+		self.finished = self.pixels.peek().is_none();
+		self.next_frame += self.step;
+
+		Some(Snapshot {
+			colors: Bytes::copy_from_slice(&self.colors),
+			taken_at: frame_time,
+			..self.start.clone()
+		})
+	}
+}
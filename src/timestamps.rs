@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// When each pixel was last placed, in seconds since the canvas started, or 0
+// for pixels which have never been placed. Like `AtomicBoard`, the values are
+// atomic so that placements can be applied in place while others read them,
+// see `Client::timestamps`.
+#[derive(Debug)]
+pub struct Timestamps {
+	cells: Cells,
+}
+
+#[derive(Debug)]
+enum Cells {
+	Heap(Box<[AtomicU32]>),
+	#[cfg(feature = "mmap")]
+	Mapped(MappedCells),
+}
+
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+struct MappedCells {
+	// only ever accessed through `cells`, it's kept here so the mapping stays
+	// alive
+	_map: memmap2::MmapMut,
+	cells: *const AtomicU32,
+	len: usize,
+}
+
+// SAFETY: the mapping is owned by the struct and only accessed through
+// atomics, which are safe to share between threads.
+#[cfg(feature = "mmap")]
+unsafe impl Send for MappedCells {}
+#[cfg(feature = "mmap")]
+unsafe impl Sync for MappedCells {}
+
+impl Timestamps {
+	pub fn new(timestamps: Vec<u32>) -> Self {
+		let cells = timestamps.into_iter().map(AtomicU32::new).collect();
+		Self { cells: Cells::Heap(cells) }
+	}
+
+	// Keeps the timestamps in a file at `path` rather than in memory, in
+	// native byte order. The file is replaced rather than written to and from
+	// then on holds the current timestamps.
+	#[cfg(feature = "mmap")]
+	pub fn mapped(timestamps: &[u32], path: &std::path::Path) -> std::io::Result<Self> {
+		if timestamps.is_empty() {
+			// empty files can't be mapped
+			return Ok(Self::new(vec![]));
+		}
+
+		// SAFETY: any initialized u32 is also valid as bytes
+		let bytes = unsafe {
+			std::slice::from_raw_parts(timestamps.as_ptr() as *const u8, std::mem::size_of_val(timestamps))
+		};
+		let mut map = crate::board::map_file(path, bytes)?;
+		let mapped = MappedCells {
+			cells: map.as_mut_ptr() as *const AtomicU32,
+			len: timestamps.len(),
+			_map: map,
+		};
+
+		Ok(Self { cells: Cells::Mapped(mapped) })
+	}
+
+	fn as_slice(&self) -> &[AtomicU32] {
+		match &self.cells {
+			Cells::Heap(cells) => cells,
+			// SAFETY: AtomicU32 has the same layout as u32, mappings are page
+			// aligned, the file holds exactly `len` values, the pointer came
+			// from a mutable borrow of the mapping and the mapping lives as
+			// long as self.
+			#[cfg(feature = "mmap")]
+			Cells::Mapped(mapped) => unsafe {
+				std::slice::from_raw_parts(mapped.cells, mapped.len)
+			},
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.as_slice().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.as_slice().is_empty()
+	}
+
+	// The timestamp at an index into the board, see `Pos::index`.
+	pub fn get(&self, index: usize) -> Option<u32> {
+		self.as_slice().get(index)
+			.map(|cell| cell.load(Ordering::Relaxed))
+	}
+
+	pub(crate) fn set_index(&self, index: usize, timestamp: u32) {
+		if let Some(cell) = self.as_slice().get(index) {
+			cell.store(timestamp, Ordering::Relaxed);
+		}
+	}
+
+	// Updates made while copying may or may not be included.
+	pub fn to_vec(&self) -> Vec<u32> {
+		self.as_slice().iter()
+			.map(|cell| cell.load(Ordering::Relaxed))
+			.collect()
+	}
+}
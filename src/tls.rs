@@ -0,0 +1,100 @@
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+use openssl::ssl::{SslConnector, SslConnectorBuilder, SslMethod, SslVerifyMode};
+use openssl::x509::{X509, X509Ref};
+
+// sha256 digest of a DER encoded certificate or public key
+pub type Fingerprint = [u8; 32];
+
+#[derive(Debug, Clone, Default)]
+struct Pins {
+	certificates: Vec<Fingerprint>,
+	public_keys: Vec<Fingerprint>,
+}
+
+impl Pins {
+	fn is_empty(&self) -> bool {
+		self.certificates.is_empty() && self.public_keys.is_empty()
+	}
+
+	fn matches(&self, cert: &X509Ref) -> bool {
+		let certificate = cert.to_der()
+			.and_then(|der| hash(MessageDigest::sha256(), &der));
+		if let Ok(digest) = certificate {
+			if self.certificates.iter().any(|pin| pin[..] == digest[..]) {
+				return true;
+			}
+		}
+
+		let public_key = cert.public_key()
+			.and_then(|key| key.public_key_to_der())
+			.and_then(|der| hash(MessageDigest::sha256(), &der));
+		if let Ok(digest) = public_key {
+			if self.public_keys.iter().any(|pin| pin[..] == digest[..]) {
+				return true;
+			}
+		}
+
+		false
+	}
+}
+
+// Extra trust settings for servers which don't use a publicly trusted
+// certificate, applied to both REST requests and the websocket.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TlsConfig {
+	// PEM encoded, parsed when the client is built
+	roots: Vec<Vec<u8>>,
+	pins: Pins,
+	accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+	pub fn add_root_certificate(&mut self, pem: Vec<u8>) {
+		self.roots.push(pem);
+	}
+
+	pub fn pin_certificate(&mut self, fingerprint: Fingerprint) {
+		self.pins.certificates.push(fingerprint);
+	}
+
+	pub fn pin_public_key(&mut self, fingerprint: Fingerprint) {
+		self.pins.public_keys.push(fingerprint);
+	}
+
+	pub fn danger_accept_invalid_certs(&mut self, accept: bool) {
+		self.accept_invalid_certs = accept;
+	}
+
+	pub fn connector(&self) -> Result<SslConnectorBuilder, ErrorStack> {
+		let mut builder = SslConnector::builder(SslMethod::tls())?;
+
+		for pem in &self.roots {
+			for cert in X509::stack_from_pem(pem)? {
+				builder.cert_store_mut().add_cert(cert)?;
+			}
+		}
+
+		if !self.pins.is_empty() {
+			// pins are checked once the rest of the chain has been verified, so
+			// a pinned certificate still needs to be trusted and valid
+			let pins = self.pins.clone();
+			builder.set_verify_callback(SslVerifyMode::PEER, move |preverified, context| {
+				if !preverified || context.error_depth() != 0 {
+					return preverified;
+				}
+
+				context.chain()
+					.map(|chain| chain.iter().any(|cert| pins.matches(cert)))
+					.unwrap_or(false)
+			});
+		}
+
+		if self.accept_invalid_certs {
+			// replaces the pinning callback too, nothing is checked at all
+			builder.set_verify(SslVerifyMode::NONE);
+		}
+
+		Ok(builder)
+	}
+}
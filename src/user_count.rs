@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::export::serialize_millis;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserCountSample {
+	#[serde(serialize_with = "serialize_millis")]
+	pub time: SystemTime,
+	pub count: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct UserCountSummary {
+	pub min: usize,
+	pub max: usize,
+	// weighted by how long each count lasted
+	pub average: f64,
+}
+
+// The most recent user counts sent by the server, which only sends one when
+// the count changes.
+#[derive(Debug)]
+pub(crate) struct UserCountHistory {
+	capacity: usize,
+	samples: Mutex<VecDeque<UserCountSample>>,
+}
+
+impl UserCountHistory {
+	pub fn new(capacity: usize) -> Self {
+		Self { capacity, samples: Mutex::new(VecDeque::new()) }
+	}
+
+	pub fn record(&self, count: usize) {
+		if self.capacity == 0 {
+			return;
+		}
+
+		let mut samples = self.samples.lock().unwrap();
+		if samples.len() == self.capacity {
+			samples.pop_front();
+		}
+		samples.push_back(UserCountSample { time: SystemTime::now(), count });
+	}
+
+	pub fn samples(&self) -> Vec<UserCountSample> {
+		self.samples.lock().unwrap().iter().copied().collect()
+	}
+
+	pub fn summary(&self, window: Duration) -> Option<UserCountSummary> {
+		let now = SystemTime::now();
+		let start = now.checked_sub(window).unwrap_or(SystemTime::UNIX_EPOCH);
+		let samples = self.samples.lock().unwrap();
+
+		// the last count from before the window still applies at its start
+		let first = samples.iter().rposition(|sample| sample.time <= start).unwrap_or(0);
+		let samples = samples.iter().skip(first).collect::<Vec<_>>();
+		if samples.is_empty() {
+			return None;
+		}
+
+		let ends = samples.iter().skip(1).map(|sample| sample.time)
+			.chain(std::iter::once(now));
+		let (total, weight) = samples.iter().zip(ends)
+			.map(|(sample, end)| {
+				let duration = end.duration_since(sample.time.max(start))
+					.unwrap_or_default()
+					.as_secs_f64();
+				(sample.count as f64 * duration, duration)
+			})
+			.fold((0.0, 0.0), |(total, weight), (count, duration)| (total + count, weight + duration));
+
+		let average = if weight > 0.0 {
+			total / weight
+		} else {
+			samples.iter().map(|sample| sample.count as f64).sum::<f64>() / samples.len() as f64
+		};
+
+		Some(UserCountSummary {
+			min: samples.iter().map(|sample| sample.count).min()?,
+			max: samples.iter().map(|sample| sample.count).max()?,
+			average,
+		})
+	}
+}
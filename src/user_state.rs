@@ -0,0 +1,82 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::messages::{Message, PlacementOverrides, Role};
+
+// What the server has said about the signed in user on this connection,
+// starting from userinfo and kept up to date by later messages.
+#[derive(Debug, Clone)]
+pub struct UserState {
+	pub username: String,
+	pub roles: Vec<Role>,
+	pub pixel_count: usize,
+	pub pixel_count_all_time: usize,
+	pub banned: bool,
+	// milliseconds since the unix epoch
+	pub ban_expiry: Option<u64>,
+	pub ban_reason: Option<String>,
+	pub method: String,
+	pub placement_overrides: PlacementOverrides,
+	pub chat_banned: bool,
+	pub chatban_reason: Option<String>,
+	pub chatban_is_perma: Option<bool>,
+	// milliseconds since the unix epoch
+	pub chatban_expiry: Option<u64>,
+	pub rename_requested: bool,
+	pub discord_name: Option<String>,
+	pub chat_name_color: isize,
+}
+
+fn now_millis() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH)
+		.map(|time| time.as_millis() as u64)
+		.unwrap_or_default()
+}
+
+impl UserState {
+	pub(crate) fn from_message(message: &Message) -> Option<Self> {
+		match message.clone() {
+			Message::Userinfo { username, roles, pixel_count, pixel_count_all_time, banned, ban_expiry, ban_reason, method, placement_overrides, chat_banned, chatban_reason, chatban_is_perma, chatban_expiry, rename_requested, discord_name, chat_name_color } => {
+				Some(Self { username, roles, pixel_count, pixel_count_all_time, banned, ban_expiry, ban_reason, method, placement_overrides, chat_banned, chatban_reason, chatban_is_perma, chatban_expiry, rename_requested, discord_name, chat_name_color })
+			},
+			_ => None,
+		}
+	}
+
+	pub(crate) fn update(&mut self, message: &Message) {
+		match message {
+			Message::PixelCounts { pixel_count, pixel_count_all_time } => {
+				self.pixel_count = *pixel_count;
+				self.pixel_count_all_time = *pixel_count_all_time;
+			},
+			Message::AdminPlacementOverrides { placement_overrides } => {
+				self.placement_overrides = placement_overrides.clone();
+			},
+			Message::ChatBan { permanent, reason, expiry }
+			| Message::ChatBanState { permanent, reason, expiry } => {
+				self.chat_banned = *permanent || *expiry > now_millis();
+				self.chatban_is_perma = Some(*permanent);
+				self.chatban_reason = Some(reason.clone());
+				self.chatban_expiry = Some(*expiry);
+			},
+			Message::Rename { requested } => {
+				self.rename_requested = *requested;
+			},
+			Message::RenameSuccess { new_name } => {
+				self.username = new_name.clone();
+				self.rename_requested = false;
+			},
+			_ => (),
+		}
+	}
+
+	// Whether the user is banned from placing right now, accounting for
+	// temporary bans which have run out since userinfo was sent.
+	pub fn is_banned(&self) -> bool {
+		self.banned && self.ban_expiry.is_none_or(|expiry| expiry > now_millis())
+	}
+
+	pub fn is_chat_banned(&self) -> bool {
+		self.chat_banned && (self.chatban_is_perma == Some(true)
+			|| self.chatban_expiry.is_none_or(|expiry| expiry > now_millis()))
+	}
+}
@@ -0,0 +1,76 @@
+use bytes::Bytes;
+use pxlsspace_api::*;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+fn dump() -> Dump {
+	Dump {
+		canvas_code: "7a".into(),
+		width: 3,
+		height: 2,
+		palette: vec![
+			Color { name: "White".to_owned(), value: [255, 255, 255] },
+			Color { name: "Red".to_owned(), value: [255, 0, 0] },
+		],
+		taken_at: UNIX_EPOCH + Duration::from_millis(123456),
+		colors: Bytes::from_static(&[0, 1, 0, 1, 1, 0]),
+		initial: None,
+		mask: Some(Bytes::from_static(&[0; 6])),
+		virginmap: None,
+		heatmap: None,
+	}
+}
+
+fn exported() -> Vec<u8> {
+	let mut bytes = vec![];
+	dump().export(&mut bytes).unwrap();
+	bytes
+}
+
+// where the width is in `exported()`: magic, version, time and canvas code
+const WIDTH_OFFSET: usize = 8 + 1 + 8 + 2 + 2;
+
+#[test]
+fn roundtrip() {
+	let original = dump();
+	let imported = Dump::import(&exported()[..]).unwrap();
+	assert_eq!(imported.canvas_code, original.canvas_code);
+	assert_eq!((imported.width, imported.height), (3, 2));
+	assert_eq!(imported.palette[1].value, [255, 0, 0]);
+	assert_eq!(imported.taken_at, original.taken_at);
+	assert_eq!(imported.colors, original.colors);
+	assert_eq!(imported.mask, original.mask);
+	assert_eq!(imported.initial, None);
+}
+
+#[test]
+fn not_a_dump() {
+	assert!(matches!(Dump::import(&b"PXLSDUMQ\x01"[..]), Err(DumpError::NotADump)));
+	assert!(matches!(Dump::import(&b"PXLS"[..]), Err(DumpError::Io(_))));
+}
+
+#[test]
+fn truncated_header() {
+	let bytes = exported();
+	for length in [9, WIDTH_OFFSET + 2, WIDTH_OFFSET + 6] {
+		let result = Dump::import(&bytes[..length]);
+		assert!(matches!(result, Err(DumpError::Io(_))), "{:?}", result);
+	}
+}
+
+#[test]
+fn truncated_buffer() {
+	let bytes = exported();
+	let result = Dump::import(&bytes[..bytes.len() - 1]);
+	assert!(matches!(result, Err(DumpError::BufferSize { expected: 6, actual: 5 })), "{:?}", result);
+}
+
+#[test]
+fn oversized_header() {
+	// a board of u32::MAX by u32::MAX with only the original six bytes of
+	// data fails on the data rather than trying to allocate the whole board
+	let mut bytes = exported();
+	bytes[WIDTH_OFFSET..WIDTH_OFFSET + 8].fill(0xff);
+	let result = Dump::import(&bytes[..]);
+	assert!(matches!(result, Err(DumpError::BufferSize { actual, .. }) if actual < 64), "{:?}", result);
+}
@@ -0,0 +1,383 @@
+#![cfg(feature = "test-util")]
+
+use pxlsspace_api::*;
+use pxlsspace_api::test_util::*;
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+// a 4x2 canvas with code 70 whose chat rate limit message is "slow down"
+const INFO: &str = include_str!("fixtures/info.json");
+
+async fn within<F: Future>(future: F) -> F::Output {
+	tokio::time::timeout(Duration::from_secs(5), future).await
+		.expect("timed out")
+}
+
+async fn start(client: Client) -> Arc<Client> {
+	let client = Arc::new(client);
+	let running = client.clone();
+	tokio::spawn(async move { running.start().await });
+	client
+}
+
+fn sent(server: &MockServer, kind: &str) -> Vec<String> {
+	let kind = format!(r#""type":"{}""#, kind);
+	server.received().into_iter()
+		.filter(|message| message.contains(&kind))
+		.collect()
+}
+
+fn userinfo(username: &str) -> String {
+	format!(r#"{{"type":"userinfo","username":"{}","pixelCount":0,"banned":false,"banExpiry":null,"banReason":null,"chatbanReason":null,"chatbanIsPerma":null,"chatbanExpiry":null}}"#, username)
+}
+
+fn echo(author: &str, message: &str) -> String {
+	format!(r#"{{"type":"chat_message","message":{{"id":1,"author":"{}","date":0,"message_raw":"{}","purge":null,"badges":[],"authorNameColor":0,"authorWasShadowBanned":null,"strippedFaction":null}}}}"#, author, message)
+}
+
+fn cooldown(message: &str) -> String {
+	format!(r#"{{"type":"message_cooldown","diff":1,"message":"{}"}}"#, message)
+}
+
+#[tokio::test]
+async fn chat_resent_only_when_rejected() {
+	let server = MockServer::builder().info(INFO).start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.chat_interval(Duration::from_millis(100))
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	client.send_chat("first").await.unwrap();
+	within(client.flush_chat()).await;
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	assert_eq!(sent(&server, "ChatMessage").len(), 1);
+
+	// a cooldown for some other message only delays chat
+	server.push(cooldown("something else"));
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+	assert_eq!(sent(&server, "ChatMessage").len(), 1);
+
+	// one naming our message means it was rejected
+	server.push(cooldown("first"));
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+	let chat = sent(&server, "ChatMessage");
+	assert_eq!(chat.len(), 2);
+	assert!(chat[1].contains("first"));
+
+	// as does the server's generic rate limit message
+	client.send_chat("second").await.unwrap();
+	within(client.flush_chat()).await;
+	server.push(cooldown("slow down"));
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+	let chat = sent(&server, "ChatMessage");
+	assert_eq!(chat.len(), 4);
+	assert!(chat[3].contains("second"));
+}
+
+#[tokio::test]
+async fn chat_not_resent_once_echoed() {
+	let server = MockServer::builder().info(INFO).start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+	server.push(userinfo("someone"));
+	within(recorder.wait_for(|event| matches!(event, RecordedEvent::Message(Message::Userinfo { .. })))).await;
+
+	client.send_chat("hello").await.unwrap();
+	within(client.flush_chat()).await;
+	server.push(echo("someone", "hello"));
+	within(recorder.wait_for(|event| matches!(event, RecordedEvent::Message(Message::ChatMessage { .. })))).await;
+
+	server.push(cooldown("hello"));
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+	assert_eq!(sent(&server, "ChatMessage").len(), 1);
+}
+
+#[tokio::test]
+async fn chat_echo_from_unknown_user_not_trusted() {
+	let server = MockServer::builder().info(INFO).start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	// without userinfo there's no telling whether this was us
+	client.send_chat("hello").await.unwrap();
+	within(client.flush_chat()).await;
+	server.push(echo("someone", "hello"));
+	within(recorder.wait_for(|event| matches!(event, RecordedEvent::Message(Message::ChatMessage { .. })))).await;
+
+	server.push(cooldown("hello"));
+	tokio::time::sleep(Duration::from_millis(1500)).await;
+	assert_eq!(sent(&server, "ChatMessage").len(), 2);
+}
+
+#[tokio::test]
+async fn chat_refused_when_disabled() {
+	let info = INFO.replace(r#""chatEnabled":true"#, r#""chatEnabled":false"#);
+	let server = MockServer::builder().info(info).start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	assert!(!client.capabilities().chat);
+	assert!(matches!(client.send_chat("hello").await, Err(ChatError::Disabled)));
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert!(sent(&server, "ChatMessage").is_empty());
+}
+
+#[test]
+fn zero_placement_rate_window_refused() {
+	let built = Client::builder()
+		.site("http://localhost/".parse().unwrap())
+		.event_handler(RecordingHandler::new())
+		.placement_rate_window(Duration::ZERO)
+		.build();
+	assert!(matches!(built, Err(ClientBuildError::ZeroPlacementRateWindow)));
+}
+
+#[tokio::test]
+async fn unplaceable_pixels_refused() {
+	// only the top left pixel can be placed on
+	let mut mask = vec![0xFF; 8];
+	mask[0] = 0;
+	let server = MockServer::builder()
+		.info(INFO)
+		.mask(mask)
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.refuse_unplaceable(true)
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	let refused = client.place(Pixel { position: Pos::new(1, 1), color: 1 }).await;
+	assert!(matches!(refused, Err(PlaceError::Unplaceable(position)) if position == Pos::new(1, 1)));
+	let outside = client.place(Pixel { position: Pos::new(10, 10), color: 1 }).await;
+	assert!(matches!(outside, Err(PlaceError::Unplaceable(_))));
+
+	client.place(Pixel { position: Pos::new(0, 0), color: 1 }).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert_eq!(sent(&server, "pixel").len(), 1);
+	assert_eq!(client.placement_queue_len().await, 0);
+}
+
+#[tokio::test]
+async fn unplaceable_pixels_sent_by_default() {
+	let server = MockServer::builder()
+		.info(INFO)
+		.mask(vec![0xFF; 8])
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	client.place(Pixel { position: Pos::new(1, 1), color: 1 }).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert_eq!(sent(&server, "pixel").len(), 1);
+}
+
+#[tokio::test]
+async fn not_connected_until_info_loads() {
+	// no info route, so every connection attempt fails after the websocket opens
+	let server = MockServer::builder().start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.reconnect_policy(FixedDelay(Duration::from_millis(100)))
+		.build().unwrap();
+	let client = start(client).await;
+
+	let attempts = || server.requests().iter().filter(|path| *path == "/ws").count();
+	for _ in 0..50 {
+		if attempts() >= 3 {
+			break;
+		}
+		assert!(!client.is_connected().await);
+		tokio::time::sleep(Duration::from_millis(50)).await;
+	}
+
+	assert!(attempts() >= 3, "the client should keep retrying");
+	assert!(!client.is_connected().await);
+	assert!(!recorder.events().iter().any(|event| matches!(event, RecordedEvent::Ready)));
+	// sending waits for a connection rather than using the abandoned one
+	assert_eq!(client.send_chat("hello").await.unwrap(), 1);
+	assert!(server.received().is_empty());
+}
+
+#[tokio::test]
+async fn color_snapshots_shared_until_changed() {
+	let server = MockServer::builder()
+		.info(INFO)
+		.colors(vec![0, 1, 0, 1, 0, 0, 0, 0])
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	let colors = client.colors().await.unwrap();
+	assert!(Arc::ptr_eq(&colors, &client.colors().await.unwrap()));
+
+	server.push(r#"{"type":"pixel","pixels":[{"x":3,"y":1,"color":1},{"x":0,"y":0,"color":1}]}"#);
+	within(recorder.wait_for_pixel((0, 0))).await;
+	let updated = client.colors().await.unwrap();
+	assert_eq!(&colors[..], [0, 1, 0, 1, 0, 0, 0, 0]);
+	assert_eq!(&updated[..], [1, 1, 0, 1, 0, 0, 0, 1]);
+	assert!(Arc::ptr_eq(&updated, &client.colors().await.unwrap()));
+}
+
+#[tokio::test]
+async fn positions_from_acknowledgements_and_lookups() {
+	let server = MockServer::builder()
+		.info(INFO)
+		.route("lookup", r#"{"id":5,"x":2,"y":1,"time":0,"username":"someone","discordName":null,"faction":null,"origin":null,"pixel_count":1,"pixel_count_alltime":1,"extra":true}"#)
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	server.push(r#"{"type":"ACK","ackFor":"PLACE","x":3,"y":1}"#);
+	within(recorder.wait_for(|event| matches!(
+		event,
+		RecordedEvent::Message(Message::Acknowledge { x: 3, y: 1, .. }),
+	))).await;
+
+	let lookup = client.lookup(Pos::new(2, 1)).await.unwrap().unwrap();
+	assert_eq!(lookup.position, Pos::new(2, 1));
+	assert_eq!(lookup.extra.get("extra"), Some(&serde_json::Value::Bool(true)));
+	assert!(!lookup.extra.contains_key("x"));
+}
+
+#[tokio::test]
+async fn rename_failure_status_reported() {
+	let server = MockServer::builder().info(INFO).start().unwrap();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(RecordingHandler::new())
+		.build().unwrap();
+
+	let renamed = client.execute_rename("someone").await;
+	assert!(matches!(
+		renamed,
+		Err(RenameError::Request(RequestError::Status(status))) if status.as_u16() == 404,
+	));
+}
+
+#[cfg(feature = "mmap")]
+#[tokio::test]
+async fn mapped_board_survives_refresh() {
+	let dir = tempdir("board");
+	let server = MockServer::builder()
+		.info(INFO)
+		.colors(vec![0, 1, 0, 1, 0, 0, 0, 0])
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.board_storage(BoardStorage::Mapped(dir.clone()))
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	let board = client.board().await.unwrap();
+	server.push(r#"{"type":"pixel","pixels":[{"x":3,"y":1,"color":5}]}"#);
+	within(recorder.wait_for_pixel((3, 1))).await;
+	assert_eq!(board.get(3, 1), Some(5));
+	assert_eq!(std::fs::read(dir.join("70.colors")).unwrap(), board.to_bytes());
+
+	// the file is replaced rather than rewritten, so the old board stays intact
+	let refreshed = client.refresh_board().await.unwrap();
+	assert_eq!(board.to_bytes(), [0, 1, 0, 1, 0, 0, 0, 5][..]);
+	assert_eq!(refreshed.to_bytes(), [0, 1, 0, 1, 0, 0, 0, 0][..]);
+	assert_eq!(std::fs::read(dir.join("70.colors")).unwrap(), refreshed.to_bytes());
+
+	std::fs::remove_dir_all(dir).ok();
+}
+
+#[cfg(feature = "mmap")]
+#[tokio::test]
+async fn mapped_timestamps() {
+	let dir = tempdir("timestamps");
+	let server = MockServer::builder()
+		.info(INFO)
+		.heatmap(vec![0, 10, 0, 0, 0, 0, 0, 0])
+		.virginmap(vec![1, 0, 1, 1, 1, 1, 1, 0])
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.board_storage(BoardStorage::Mapped(dir.clone()))
+		.build().unwrap();
+	let client = start(client).await;
+	within(recorder.wait_for_ready()).await;
+
+	let timestamps = client.timestamps().await.unwrap();
+	assert_eq!(timestamps.len(), 8);
+	assert_eq!(timestamps.get(0), Some(0));
+	assert!(timestamps.get(1).unwrap() > 0);
+
+	let file = std::fs::read(dir.join("70.timestamps")).unwrap();
+	let stored = file.chunks_exact(4)
+		.map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+		.collect::<Vec<_>>();
+	assert_eq!(stored, timestamps.to_vec());
+
+	// placements are written straight to the file
+	server.push(r#"{"type":"pixel","pixels":[{"x":2,"y":0,"color":1}]}"#);
+	within(recorder.wait_for_pixel((2, 0))).await;
+	let placed = timestamps.get(2).unwrap();
+	assert!(placed > timestamps.get(1).unwrap());
+	let file = std::fs::read(dir.join("70.timestamps")).unwrap();
+	assert_eq!(u32::from_ne_bytes(file[8..12].try_into().unwrap()), placed);
+
+	// and a refresh replaces the file, leaving the old timestamps intact
+	let refreshed = client.refresh_timestamps().await.unwrap();
+	assert_eq!(refreshed.get(2), Some(0));
+	assert_eq!(timestamps.get(2), Some(placed));
+
+	std::fs::remove_dir_all(dir).ok();
+}
+
+#[cfg(feature = "mmap")]
+fn tempdir(name: &str) -> std::path::PathBuf {
+	let dir = std::env::temp_dir()
+		.join(format!("pxlsspace-api-{}-{}", name, std::process::id()));
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::create_dir_all(&dir).unwrap();
+	dir
+}
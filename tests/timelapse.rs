@@ -0,0 +1,37 @@
+use pxlsspace_api::*;
+
+use bytes::Bytes;
+
+use std::time::{Duration, SystemTime};
+
+fn snapshot() -> Snapshot {
+	Snapshot {
+		canvas_code: CanvasCode::new("70"),
+		width: 2,
+		height: 1,
+		palette: vec![],
+		colors: Bytes::from_static(&[0, 0]),
+		taken_at: SystemTime::UNIX_EPOCH,
+	}
+}
+
+#[test]
+fn zero_step_refused() {
+	assert!(Timelapse::new(snapshot(), Duration::ZERO).is_none());
+}
+
+#[test]
+fn frames_every_step() {
+	let start = SystemTime::UNIX_EPOCH;
+	let pixels = vec![
+		(start + Duration::from_secs(1), Pixel { position: Pos::new(0, 0), color: 1 }),
+		(start + Duration::from_secs(25), Pixel { position: Pos::new(1, 0), color: 2 }),
+	];
+
+	let timelapse = Timelapse::new(snapshot(), Duration::from_secs(10)).unwrap();
+	let frames = timelapse.frames(pixels).collect::<Vec<_>>();
+	let colors = frames.iter()
+		.map(|frame| frame.colors.to_vec())
+		.collect::<Vec<_>>();
+	assert_eq!(colors, [vec![1, 0], vec![1, 0], vec![1, 2]]);
+}
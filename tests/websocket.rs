@@ -0,0 +1,69 @@
+#![cfg(feature = "test-util")]
+
+use pxlsspace_api::*;
+use pxlsspace_api::test_util::*;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+const INFO: &str = include_str!("fixtures/info.json");
+
+fn pixel(x: u32, y: u32) -> String {
+	format!(r#"{{"type":"pixel","pixels":[{{"x":{},"y":{},"color":1}}]}}"#, x, y)
+}
+
+async fn start(client: Client) -> Arc<Client> {
+	let client = Arc::new(client);
+	let running = client.clone();
+	tokio::spawn(async move { running.start().await });
+	client
+}
+
+#[tokio::test]
+async fn compressed_messages() {
+	// large enough to need a 16 bit length once inflated
+	let many = format!(
+		r#"{{"type":"pixel","pixels":[{}{{"x":2,"y":0,"color":1}}]}}"#,
+		r#"{"x":1,"y":1,"color":1},"#.repeat(2000),
+	);
+	let server = MockServer::builder()
+		.info(INFO)
+		.compress()
+		.send(pixel(1, 1))
+		.send(many)
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.websocket_compression(true)
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let client = start(client).await;
+
+	tokio::time::timeout(Duration::from_secs(5), recorder.wait_for_pixel((2, 0))).await.unwrap();
+	// the compression window carries over between messages
+	server.push(pixel(3, 0));
+	tokio::time::timeout(Duration::from_secs(5), recorder.wait_for_pixel((3, 0))).await.unwrap();
+
+	// messages to the server aren't compressed
+	client.send_chat("hello").await.unwrap();
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert!(server.received().iter().any(|message| message.contains("hello")));
+}
+
+#[tokio::test]
+async fn compression_not_offered() {
+	let server = MockServer::builder()
+		.info(INFO)
+		.compress()
+		.send(pixel(1, 1))
+		.start().unwrap();
+	let recorder = RecordingHandler::new();
+	let client = Client::builder()
+		.site(server.url())
+		.event_handler(recorder.clone())
+		.build().unwrap();
+	let _client = start(client).await;
+
+	tokio::time::timeout(Duration::from_secs(5), recorder.wait_for_pixel((1, 1))).await.unwrap();
+}